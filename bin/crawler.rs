@@ -0,0 +1,155 @@
+//! Runs all of the crawlers, the uploader and the api in one process
+//!
+//!     crawler run-all
+//!     crawler <dogs|goals|upload|api|notify>
+//!     crawler check-selectors <url|file> [--page contest|search|entry]
+//!     crawler replay-uploads
+//!
+//! The tasks share their crawls in memory, so with `--write-files false`
+//! the crawls never have to go through the disk.
+//!
+//! Every task gets restarted with a backoff when it fails, and on ctrl-c or
+//! SIGTERM they all finish what they are writing before the process exits.
+//! `check-selectors` runs the selector profile against a live or saved page
+//! and reports which of the fields resolved, `replay-uploads` pushes whatever
+//! is still waiting in the upload spool after an outage.
+
+use std::{error::Error, time::Duration};
+
+use oshkosh_kiwanis_web_crawler::{
+    config,
+    fetch::{CrawlLimits, Fetcher},
+    selectors::{PageKind, SelectorProfile},
+    snapshots::SnapshotStore,
+    tasks::{self, Task, TaskStatuses},
+    Contests,
+};
+use tokio::{runtime::Handle, task::LocalSet};
+
+use log::{error, info};
+
+const USAGE: &str = "usage: crawler <run-all|dogs|goals|upload|api|notify> [--contests <file>] [--history-db <file>] [--output-dir <dir>] [--write-files true|false]
+                [--upload-sink gs://bucket|s3://bucket|file://dir] [--upload-heartbeat-hours <n>]
+                [--export-formats ndjson,parquet] [--export-dir <dir>] [--notify <url>,...] [--forecast-window-hours <n>]
+                [--idle-interval-minutes <n>]
+       crawler check-selectors <url|file> [--page contest|search|entry] [--selectors <file>]
+       crawler replay-uploads [--spool-dir <dir>] [--upload-sink <url>]";
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+
+    let command = std::env::args().nth(1).unwrap_or_default();
+    if command == "check-selectors" {
+        return check_selectors().await;
+    }
+    if command == "replay-uploads" {
+        return replay_uploads().await;
+    }
+
+    let to_run: Vec<Task> = match command.as_str() {
+        "run-all" => Task::ALL.to_vec(),
+        name => match Task::from_name(name) {
+            Some(task) => vec![task],
+            None => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        },
+    };
+
+    let contests = match Contests::load() {
+        Ok(contests) => contests,
+        Err(e) => {
+            error!("Unable to load contests; error={}", e);
+            return Err(e.into());
+        }
+    };
+
+    let shutdown = tasks::shutdown_on_signals();
+    let statuses = TaskStatuses::default();
+    // the api and the goals crawler get the crawls from here instead of from the files
+    let store = SnapshotStore::default();
+
+    // every task gets a thread of its own, so the sqlite, file and export writes one of them
+    // blocks on don't hold up the others. The tasks don't have to be Send this way, and the
+    // api brings its own actix system on top of that.
+    let handles: Vec<_> = to_run
+        .into_iter()
+        .map(|task| {
+            let (contests, store, statuses, shutdown) = (contests.clone(), store.clone(), statuses.clone(), shutdown.clone());
+            tokio::task::spawn_blocking(move || {
+                let local = LocalSet::new();
+                Handle::current().block_on(local.run_until(tasks::supervise(task, contests, store, statuses, shutdown)))
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        let _ = handle.await;
+    }
+
+    info!("all tasks stopped");
+    Ok(())
+}
+
+async fn check_selectors() -> Result<(), Box<dyn Error>> {
+    let target = match std::env::args().nth(2).filter(|arg| !arg.starts_with("--")) {
+        Some(target) => target,
+        None => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let kind = match config::setting("--page", "CHECK_SELECTORS_PAGE") {
+        Some(name) => match PageKind::from_name(&name) {
+            Some(kind) => kind,
+            None => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        },
+        None => PageKind::guess(&target),
+    };
+
+    let selectors = SelectorProfile::load()?;
+    let html = if target.starts_with("http://") || target.starts_with("https://") {
+        Fetcher::new(CrawlLimits::default(), Duration::from_secs(30))?.get_text(&target).await?
+    } else {
+        std::fs::read_to_string(&target)?
+    };
+
+    println!("checking {} page; {}", kind.name(), target);
+    let checks = selectors.check(kind, &html);
+    for check in checks.iter() {
+        let status = match (check.resolved(), check.required) {
+            (true, _) => "ok",
+            (false, true) => "MISSING",
+            (false, false) => "missing",
+        };
+        let value: String = check.value.as_deref().unwrap_or("-").chars().take(40).collect();
+
+        println!("{:<8} {:<15} {:<3} {:<42} {}", status, check.field, check.matches, value, check.selector);
+    }
+
+    let broken = checks.iter().filter(|check| check.required && !check.resolved()).count();
+    if broken > 0 {
+        eprintln!("{} required field(s) did not resolve", broken);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+async fn replay_uploads() -> Result<(), Box<dyn Error>> {
+    let drained = tasks::upload::replay().await?;
+    println!("uploaded {} file(s), {} still waiting", drained.uploaded, drained.pending);
+
+    if let Some((file, e)) = drained.failed {
+        eprintln!("unable to upload {}: {}", file, e);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
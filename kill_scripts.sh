@@ -1 +1,2 @@
-kill 676
+# SIGTERM lets the crawler finish writing whatever it is in the middle of before it exits
+kill -TERM $(cat crawler.pid) && rm crawler.pid
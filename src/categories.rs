@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::Contest;
+
+/// How a category was matched to a contest, from the strictest to the loosest
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    // the category is exactly one of the contest's aliases
+    Exact,
+    // same as exact but ignoring upper/lower case
+    CaseInsensitive,
+    // one of the aliases shows up in the category once punctuation,
+    // possessives and plurals are stripped out
+    Normalized,
+    // like normalized but allowing a typo or two in the longer words
+    Fuzzy,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategoryMatch {
+    pub contest: Contest,
+    // the alias (or page) that matched the category
+    pub alias: String,
+    pub kind: MatchKind,
+}
+
+/// Find the contest that a scraped entry category belongs to.
+///
+/// Every contest is matched against its page and its aliases, the strictest kind of
+/// match wins and within the same kind the longest alias wins so that "Humane Society"
+/// style aliases can't steal a category from a more specific one.
+pub fn match_category(contests: &[Contest], category: &str) -> Option<CategoryMatch> {
+    let category = category.trim();
+    if category.is_empty() {
+        return None;
+    }
+
+    let category_tokens = tokens(category);
+
+    let mut best: Option<(MatchKind, usize, CategoryMatch)> = None;
+    for contest in contests {
+        for alias in std::iter::once(&contest.page).chain(contest.aliases.iter()) {
+            let kind = match match_alias(category, &category_tokens, alias) {
+                Some(kind) => kind,
+                None => continue,
+            };
+
+            let is_better = match &best {
+                Some((best_kind, best_len, _)) => kind < *best_kind || (kind == *best_kind && alias.len() > *best_len),
+                None => true,
+            };
+
+            if is_better {
+                best = Some((kind, alias.len(), CategoryMatch {
+                    contest: contest.clone(),
+                    alias: alias.clone(),
+                    kind,
+                }));
+            }
+        }
+    }
+
+    best.map(|(_, _, found)| found)
+}
+
+fn match_alias(category: &str, category_tokens: &[String], alias: &str) -> Option<MatchKind> {
+    if category == alias {
+        return Some(MatchKind::Exact);
+    }
+
+    if category.to_lowercase() == alias.to_lowercase() {
+        return Some(MatchKind::CaseInsensitive);
+    }
+
+    let alias_tokens = tokens(alias);
+    if alias_tokens.is_empty() || alias_tokens.len() > category_tokens.len() {
+        return None;
+    }
+
+    let windows = || category_tokens.windows(alias_tokens.len());
+
+    if windows().any(|window| window == alias_tokens.as_slice()) {
+        return Some(MatchKind::Normalized);
+    }
+
+    let close_enough = |window: &[String]| {
+        window
+            .iter()
+            .zip(alias_tokens.iter())
+            .all(|(word, alias_word)| edit_distance(word, alias_word) <= typo_allowance(alias_word))
+    };
+
+    if windows().any(close_enough) {
+        return Some(MatchKind::Fuzzy);
+    }
+
+    None
+}
+
+// Break a category up into lowercase words with the punctuation,
+// possessives ("paws's") and plurals ("mutts") taken off
+fn tokens(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .replace("'s", "")
+        .replace('\'', "")
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            if word.len() > 3 && word.ends_with('s') {
+                word[..word.len() - 1].to_string()
+            } else {
+                word.to_string()
+            }
+        })
+        .collect()
+}
+
+// short words have to be spelled right, longer ones can have a typo or two
+fn typo_allowance(word: &str) -> usize {
+    match word.chars().count() {
+        0..=4 => 0,
+        5..=8 => 1,
+        _ => 2,
+    }
+}
+
+// plain levenshtein distance, the words we compare are tiny so there is no need to be clever
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_ch) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_ch) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_ch == *b_ch { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Keeps track of which categories we were able to match during a crawl
+/// so that any new category strings can be added as aliases
+#[derive(Debug, Default, Serialize)]
+pub struct CategoryReport {
+    // category -> the contest page it was matched to and how
+    pub matched: BTreeMap<String, (String, MatchKind)>,
+    // category -> how many dogs had it
+    pub unmatched: BTreeMap<String, usize>,
+}
+
+impl CategoryReport {
+    pub fn record(&mut self, category: &str, found: Option<&CategoryMatch>) {
+        match found {
+            Some(found) => {
+                self.matched.insert(category.to_string(), (found.contest.page.clone(), found.kind));
+            }
+            None => {
+                *self.unmatched.entry(category.to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+}
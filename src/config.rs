@@ -0,0 +1,213 @@
+use std::{error::Error, fmt, path::Path};
+
+use chrono::DateTime;
+use serde::{de::{self, DeserializeOwned}, Deserialize, Deserializer};
+
+use crate::Contest;
+
+// where we look for the contest roster if nobody tells us otherwise
+pub const DEFAULT_CONTESTS_FILE: &str = "contests.toml";
+
+// the site we crawl, tests point this at a local mock of it instead
+pub const DEFAULT_DOMAIN: &str = "https://www.gogophotocontest.com";
+
+/// Look up a setting from the command line (`--flag value` or `--flag=value`)
+/// and fall back to the environment variable if the flag was not passed.
+pub fn setting(flag: &str, env: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+
+        if let Some(value) = arg.strip_prefix(flag).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+    }
+
+    std::env::var(env).ok()
+}
+
+/// The gogophoto site to crawl, from `--domain` or `GOGOPHOTO_DOMAIN`
+pub fn domain() -> String {
+    setting("--domain", "GOGOPHOTO_DOMAIN")
+        .map(|domain| domain.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| DEFAULT_DOMAIN.to_string())
+}
+
+/// Like `setting`, but parsed, settings that do not parse are treated as missing
+pub fn parsed_setting<T: std::str::FromStr>(flag: &str, env: &str) -> Option<T> {
+    setting(flag, env).and_then(|value| value.parse().ok())
+}
+
+/// Read a point in time from a config file, either unix seconds or an RFC 3339
+/// string like `"2022-10-31T20:00:00-05:00"`, for use with `deserialize_with`
+pub fn deserialize_timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<i64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Timestamp {
+        Unix(i64),
+        Text(String),
+    }
+
+    match Option::<Timestamp>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Timestamp::Unix(seconds)) => Ok(Some(seconds)),
+        Some(Timestamp::Text(text)) => DateTime::parse_from_rfc3339(text.trim())
+            .map(|time| Some(time.timestamp()))
+            .map_err(|e| de::Error::custom(format!("{:?} is not an RFC 3339 time like \"2022-10-31T20:00:00-05:00\": {}", text, e))),
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    // the file could not be read at all
+    Io { path: String, source: std::io::Error },
+    // the file is not valid toml/json/yaml or does not have the right shape
+    Parse { path: String, message: String },
+    // one of the contests in the file does not make sense
+    Invalid { path: String, index: usize, page: String, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } => write!(f, "{}: unable to read file: {}", path, source),
+            ConfigError::Parse { path, message } => write!(f, "{}: {}", path, message),
+            ConfigError::Invalid { path, index, page, message } => {
+                // people count from one, so the first contest in the file is #1
+                write!(f, "{}: contest #{} (page={:?}): {}", path, index + 1, page, message)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// The shape of the contest roster file
+#[derive(Debug, Deserialize)]
+pub struct ContestsFile {
+    // oldest first, when the file doesn't list them they go in the order they first show up in
+    #[serde(default)]
+    pub seasons: Vec<String>,
+    pub contests: Vec<Contest>,
+}
+
+/// Read a toml, json or yaml file, picking the format from the file extension
+pub fn load_file<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigError> {
+    let display_path = path.display().to_string();
+
+    let content = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: display_path.clone(),
+        source,
+    })?;
+
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    let parsed: Result<T, String> = match extension.as_str() {
+        "json" => serde_json::from_str(&content).map_err(|e| e.to_string()),
+        "yaml" | "yml" => serde_yaml::from_str(&content).map_err(|e| e.to_string()),
+        _ => toml::from_str(&content).map_err(|e| e.to_string()),
+    };
+
+    parsed.map_err(|message| ConfigError::Parse {
+        path: display_path,
+        message,
+    })
+}
+
+/// Read the contest roster from a toml, json or yaml file,
+/// picking the format from the file extension
+pub fn load_contests(path: &Path) -> Result<ContestsFile, ConfigError> {
+    let display_path = path.display().to_string();
+    let mut file: ContestsFile = load_file(path)?;
+
+    if file.contests.is_empty() {
+        return Err(ConfigError::Parse {
+            path: display_path,
+            message: "no contests defined".into(),
+        });
+    }
+
+    if let Some(index) = (1..file.seasons.len()).find(|&index| file.seasons[..index].contains(&file.seasons[index])) {
+        return Err(ConfigError::Parse {
+            path: display_path,
+            message: format!("season {:?} is listed more than once", file.seasons[index]),
+        });
+    }
+
+    for (index, contest) in file.contests.iter().enumerate() {
+        if let Err(message) = validate_contest(contest, &file.contests[..index], &file.seasons) {
+            return Err(ConfigError::Invalid {
+                path: display_path,
+                index,
+                page: contest.page.clone(),
+                message,
+            });
+        }
+    }
+
+    if file.seasons.is_empty() {
+        for contest in file.contests.iter() {
+            if !file.seasons.contains(&contest.season) {
+                file.seasons.push(contest.season.clone());
+            }
+        }
+    }
+
+    Ok(file)
+}
+
+// Make sure a single contest makes sense, `previous` are all the contests that came
+// before it in the file so we can catch the same page being listed twice
+fn validate_contest(contest: &Contest, previous: &[Contest], seasons: &[String]) -> Result<(), String> {
+    if contest.display_name.trim().is_empty() {
+        return Err("display_name must not be empty".into());
+    }
+
+    if contest.page.is_empty() {
+        return Err("page must not be empty".into());
+    }
+
+    // the page is put straight into the gogophoto url so it has to be a plain slug
+    if !contest.page.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_') {
+        return Err("page must only contain letters, numbers, '-' or '_' (it is the gogophoto page slug)".into());
+    }
+
+    if contest.num_dogs == 0 {
+        return Err("num_dogs must be at least 1".into());
+    }
+
+    if let (Some(starts_at), Some(closes_at)) = (contest.starts_at, contest.closes_at()) {
+        if closes_at <= starts_at {
+            return Err("the contest has to start before it closes (starts_at, voting_closes_at and ends_at)".into());
+        }
+    }
+
+    if let (Some(voting_closes_at), Some(ends_at)) = (contest.voting_closes_at, contest.ends_at) {
+        if voting_closes_at > ends_at {
+            return Err("voting_closes_at must not be after ends_at".into());
+        }
+    }
+
+    if !seasons.is_empty() && !seasons.contains(&contest.season) {
+        return Err(format!("season must be one of the seasons at the top of the file: {:?}", seasons));
+    }
+
+    if let Some(other) = previous.iter().position(|c| c.page == contest.page) {
+        return Err(format!("page is already used by contest #{}", other + 1));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Works out what happened between two crawls of the top dogs, like a dog taking the lead
+//! or jumping a few hundred votes, so it can be kept in the history and shown in a feed.
+//!
+//! The ranks are within each contest, 1 is the leader. How far down the top dogs go and how
+//! big a jump in votes has to be are set with `--events-top-n`/`EVENTS_TOP_N` and
+//! `--events-vote-jump`/`EVENTS_VOTE_JUMP`.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config, EntryData};
+
+pub const DEFAULT_TOP_N: usize = 5;
+pub const DEFAULT_VOTE_JUMP: usize = 100;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    // a dog took first place in its contest
+    NewLeader,
+    // a dog moved past another one in its contest
+    Overtake,
+    // a dog made it in to the top n of its contest
+    EnteredTop,
+    // a dog dropped out of the top n of its contest, or out of the crawl altogether
+    LeftTop,
+    // a dog got at least the vote jump more votes since the last crawl
+    VoteJump,
+}
+
+impl EventKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            EventKind::NewLeader => "new_leader",
+            EventKind::Overtake => "overtake",
+            EventKind::EnteredTop => "entered_top",
+            EventKind::LeftTop => "left_top",
+            EventKind::VoteJump => "vote_jump",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<EventKind> {
+        [
+            EventKind::NewLeader,
+            EventKind::Overtake,
+            EventKind::EnteredTop,
+            EventKind::LeftTop,
+            EventKind::VoteJump,
+        ]
+        .iter()
+        .copied()
+        .find(|kind| kind.name() == name)
+    }
+}
+
+/// Something that happened to a dog between two crawls
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub contest: String,
+    pub entry_id: String,
+    pub dog: String,
+    // None when the dog wasn't in the crawl before or isn't in it anymore
+    pub previous_rank: Option<usize>,
+    pub rank: Option<usize>,
+    pub previous_votes: Option<usize>,
+    pub votes: usize,
+    // the dog that got overtaken, or the leader that got knocked off
+    pub other_entry_id: Option<String>,
+    pub other_dog: Option<String>,
+    // the tick it was noticed in
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventSettings {
+    // entering and leaving this many top dogs of a contest is an event, and so is overtaking in them
+    pub top_n: usize,
+    // 0 for no vote jump events
+    pub vote_jump: usize,
+}
+
+impl Default for EventSettings {
+    fn default() -> EventSettings {
+        EventSettings {
+            top_n: DEFAULT_TOP_N,
+            vote_jump: DEFAULT_VOTE_JUMP,
+        }
+    }
+}
+
+impl EventSettings {
+    pub fn from_settings() -> EventSettings {
+        EventSettings {
+            top_n: config::parsed_setting("--events-top-n", "EVENTS_TOP_N").unwrap_or(DEFAULT_TOP_N).max(1),
+            vote_jump: config::parsed_setting("--events-vote-jump", "EVENTS_VOTE_JUMP").unwrap_or(DEFAULT_VOTE_JUMP),
+        }
+    }
+}
+
+// a dog and where it was in its contest
+struct Ranked<'a> {
+    dog: &'a EntryData,
+    rank: usize,
+}
+
+// contest page -> the dogs of the contest in rank order, the dogs have to be sorted by votes already
+fn by_contest(dogs: &[EntryData]) -> HashMap<&str, Vec<Ranked<'_>>> {
+    let mut contests: HashMap<&str, Vec<Ranked>> = HashMap::new();
+
+    for dog in dogs {
+        let ranked = contests.entry(&dog.contest.page).or_default();
+        let rank = ranked.len() + 1;
+        ranked.push(Ranked { dog, rank });
+    }
+
+    contests
+}
+
+/// Everything that happened going from the `previous` crawl to the `current` one, both sorted by
+/// votes like `top-dogs.json`. A contest that isn't in the previous crawl has nothing to compare
+/// with, so it doesn't get any events until the crawl after.
+pub fn detect(previous: &[EntryData], current: &[EntryData], settings: EventSettings, timestamp: i64) -> Vec<Event> {
+    let previous = by_contest(previous);
+    let mut events = vec![];
+
+    let mut contests: Vec<_> = by_contest(current).into_iter().collect();
+    contests.sort_by_key(|(page, _)| *page);
+
+    for (page, now) in contests {
+        let before = match previous.get(page) {
+            Some(before) => before,
+            None => continue,
+        };
+        let find = |dogs: &[Ranked], dog: &EntryData| -> Option<usize> { dogs.iter().find(|r| r.dog.page == dog.page).map(|r| r.rank) };
+        let event = |kind, ranked: &Ranked, previous_rank: Option<usize>, previous_votes: Option<usize>, other: Option<&EntryData>| Event {
+            kind,
+            contest: page.to_string(),
+            entry_id: ranked.dog.entry_id().to_string(),
+            dog: ranked.dog.dog.clone(),
+            previous_rank,
+            rank: Some(ranked.rank),
+            previous_votes,
+            votes: ranked.dog.votes,
+            other_entry_id: other.map(|dog| dog.entry_id().to_string()),
+            other_dog: other.map(|dog| dog.dog.clone()),
+            timestamp,
+        };
+
+        for ranked in now.iter() {
+            let was = before.iter().find(|r| r.dog.page == ranked.dog.page);
+            let previous_rank = was.map(|was| was.rank);
+            let previous_votes = was.map(|was| was.dog.votes);
+
+            if ranked.rank == 1 && previous_rank != Some(1) {
+                let leader = before.first().map(|leader| leader.dog);
+                events.push(event(EventKind::NewLeader, ranked, previous_rank, previous_votes, leader));
+            }
+
+            if ranked.rank <= settings.top_n && previous_rank.is_none_or(|rank| rank > settings.top_n) {
+                events.push(event(EventKind::EnteredTop, ranked, previous_rank, previous_votes, None));
+            }
+
+            // everyone that was ahead of this dog and now isn't, as long as it's within the top n
+            if let Some(previous_rank) = previous_rank.filter(|_| ranked.rank <= settings.top_n) {
+                for passed in before.iter().filter(|r| r.rank < previous_rank) {
+                    if find(&now, passed.dog).is_some_and(|rank| rank > ranked.rank) {
+                        events.push(event(EventKind::Overtake, ranked, Some(previous_rank), previous_votes, Some(passed.dog)));
+                    }
+                }
+            }
+
+            if let Some(votes) = previous_votes {
+                if settings.vote_jump > 0 && ranked.dog.votes >= votes + settings.vote_jump {
+                    events.push(event(EventKind::VoteJump, ranked, previous_rank, previous_votes, None));
+                }
+            }
+        }
+
+        for was in before.iter().filter(|r| r.rank <= settings.top_n) {
+            let rank = find(&now, was.dog);
+            if rank.is_none_or(|rank| rank > settings.top_n) {
+                events.push(Event {
+                    kind: EventKind::LeftTop,
+                    contest: page.to_string(),
+                    entry_id: was.dog.entry_id().to_string(),
+                    dog: was.dog.dog.clone(),
+                    previous_rank: Some(was.rank),
+                    rank,
+                    previous_votes: Some(was.dog.votes),
+                    votes: now.iter().find(|r| r.dog.page == was.dog.page).map_or(was.dog.votes, |r| r.dog.votes),
+                    other_entry_id: None,
+                    other_dog: None,
+                    timestamp,
+                });
+            }
+        }
+    }
+
+    events
+}
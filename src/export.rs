@@ -0,0 +1,489 @@
+//! The csv snapshots rolled up into bigger files for reporting, so bigquery and datastudio get a
+//! file an hour per contest instead of one tiny csv a minute. Turned on with `--export-formats`
+//! (or `EXPORT_FORMATS`), e.g. `ndjson,parquet`, and written to `--export-dir`/`EXPORT_DIR`,
+//! `export` in the output directory by default:
+//!
+//! ```text
+//! top-dogs/schema.json
+//! top-dogs/page=<page>/date=2022-10-01/top-dogs-2022-10-01T13.ndjson.gz
+//! top-dogs/page=<page>/date=2022-10-01/top-dogs-2022-10-01T13.parquet
+//! contest-goals/...
+//! ```
+//!
+//! Every tick the rows get staged in `staging`, one file per kind, contest and hour. Once the hour
+//! is over it is rolled into the export files and those go in to the upload spool under `export/`,
+//! so they end up next to the csv files in the bucket with the page and date as hive partitions.
+//! `schema.json` has the columns in the format `bq load --schema` takes.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use chrono::{DateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use log::{info, warn};
+use parquet::{
+    basic::{Compression as ParquetCompression, ConvertedType, Repetition, Type as PhysicalType},
+    data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::types::Type,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map};
+
+use crate::{
+    config::{self, ConfigError},
+    output::OutputDir,
+    spool::Spool,
+    ContestDataCSV, EntryDataCSV, Money,
+};
+
+// where the rows wait until their hour is over
+const STAGING_DIR: &str = "staging";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Ndjson,
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name.trim() {
+            "ndjson" => Some(ExportFormat::Ndjson),
+            "parquet" => Some(ExportFormat::Parquet),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "ndjson.gz",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+
+    /// A comma separated list of formats, an empty list turns the exports off
+    pub fn parse_list(list: &str) -> Result<Vec<ExportFormat>, ConfigError> {
+        let mut formats = Vec::new();
+        for name in list.split(',').filter(|name| !name.trim().is_empty()) {
+            let format = ExportFormat::from_name(name).ok_or_else(|| ConfigError::Parse {
+                path: "--export-formats".into(),
+                message: format!("the export formats are ndjson and parquet: {:?}", name),
+            })?;
+
+            if !formats.contains(&format) {
+                formats.push(format);
+            }
+        }
+
+        Ok(formats)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    String,
+    Int64,
+    // money, in dollars
+    Float64,
+}
+
+impl ColumnType {
+    /// What bigquery calls it
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColumnType::String => "STRING",
+            ColumnType::Int64 => "INT64",
+            ColumnType::Float64 => "FLOAT64",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub name: &'static str,
+    pub kind: ColumnType,
+    // whether the column can be empty
+    pub nullable: bool,
+}
+
+const fn column(name: &'static str, kind: ColumnType) -> Column {
+    Column { name, kind, nullable: false }
+}
+
+const fn nullable(name: &'static str, kind: ColumnType) -> Column {
+    Column { name, kind, nullable: true }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Int64(i64),
+    Float64(f64),
+    // only in nullable columns
+    Null,
+}
+
+// money that might not be there, like the forecasts
+fn dollars(money: Option<Money>) -> Value {
+    money.map_or(Value::Null, |money| Value::Float64(money.dollars()))
+}
+
+/// A csv row that can be exported, the values have to come in the same order as the columns
+pub trait ExportRow: Serialize + DeserializeOwned {
+    /// What the files are called, the same as the csv file
+    const KIND: &'static str;
+    const COLUMNS: &'static [Column];
+
+    fn values(&self) -> Vec<Value>;
+
+    /// The contest page it is partitioned by
+    fn page(&self) -> &str;
+}
+
+impl ExportRow for EntryDataCSV {
+    const KIND: &'static str = "top-dogs";
+    const COLUMNS: &'static [Column] = &[
+        column("display_name", ColumnType::String),
+        column("gogophoto_contest_page", ColumnType::String),
+        column("dog", ColumnType::String),
+        column("votes", ColumnType::Int64),
+        column("entry_url", ColumnType::String),
+        column("picture", ColumnType::String),
+        column("timestamp", ColumnType::Int64),
+    ];
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::String(self.display_name.clone()),
+            Value::String(self.gogophoto_contest_page.clone()),
+            Value::String(self.dog.clone()),
+            Value::Int64(self.votes as i64),
+            Value::String(self.entry_url.clone()),
+            Value::String(self.picture.clone()),
+            Value::Int64(self.timestamp),
+        ]
+    }
+
+    fn page(&self) -> &str {
+        &self.gogophoto_contest_page
+    }
+}
+
+impl ExportRow for ContestDataCSV {
+    const KIND: &'static str = "contest-goals";
+    const COLUMNS: &'static [Column] = &[
+        column("display_name", ColumnType::String),
+        column("page", ColumnType::String),
+        column("goal", ColumnType::Float64),
+        column("raised", ColumnType::Float64),
+        column("total_entries", ColumnType::Int64),
+        column("champ_day", ColumnType::Float64),
+        column("timestamp", ColumnType::Int64),
+        nullable("ends_at", ColumnType::Int64),
+        nullable("velocity_per_hour", ColumnType::Float64),
+        nullable("projected_total", ColumnType::Float64),
+        nullable("required_per_hour", ColumnType::Float64),
+    ];
+
+    fn values(&self) -> Vec<Value> {
+        vec![
+            Value::String(self.display_name.clone()),
+            Value::String(self.page.clone()),
+            Value::Float64(self.goal.dollars()),
+            Value::Float64(self.raised.dollars()),
+            Value::Int64(self.total_entries as i64),
+            Value::Float64(self.champ_day.dollars()),
+            Value::Int64(self.timestamp),
+            self.ends_at.map_or(Value::Null, Value::Int64),
+            dollars(self.velocity_per_hour),
+            dollars(self.projected_total),
+            dollars(self.required_per_hour),
+        ]
+    }
+
+    fn page(&self) -> &str {
+        &self.page
+    }
+}
+
+/// Stages the rows of every tick and rolls them into the export files once their hour is over
+#[derive(Debug, Clone)]
+pub struct Exporter {
+    dir: OutputDir,
+    formats: Vec<ExportFormat>,
+    // where the rolled files get queued for upload, they only stay in the export directory without one
+    spool: Option<Spool>,
+}
+
+impl Exporter {
+    pub fn new<P: Into<PathBuf>>(dir: P, formats: Vec<ExportFormat>) -> Exporter {
+        Exporter {
+            dir: OutputDir::new(dir),
+            formats,
+            spool: None,
+        }
+    }
+
+    pub fn with_spool(mut self, spool: Spool) -> Exporter {
+        self.spool = Some(spool);
+        self
+    }
+
+    /// The formats from `--export-formats` or `EXPORT_FORMATS`, off if neither is set, written to
+    /// `--export-dir`/`EXPORT_DIR` and queued in the upload spool
+    pub fn from_settings(output: &OutputDir) -> Result<Exporter, ConfigError> {
+        let formats = ExportFormat::parse_list(&config::setting("--export-formats", "EXPORT_FORMATS").unwrap_or_default())?;
+        let dir = config::setting("--export-dir", "EXPORT_DIR").map_or_else(|| output.path("export"), PathBuf::from);
+
+        Ok(Exporter::new(dir, formats).with_spool(Spool::from_settings(output)))
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.formats.is_empty()
+    }
+
+    pub fn dir(&self) -> &OutputDir {
+        &self.dir
+    }
+
+    /// Stage the rows of the tick at `tick` and roll every hour before it. Returns the names of the
+    /// files that got rolled, which is nothing at all most ticks.
+    pub fn export<R: ExportRow>(&self, tick: i64, rows: &[R]) -> Result<Vec<String>, Box<dyn Error>> {
+        if !self.enabled() {
+            return Ok(Vec::new());
+        }
+
+        self.stage(tick, rows)?;
+        self.roll::<R>(tick)
+    }
+
+    /// Roll every hour before `now` without staging anything, for the idle ticks, so the
+    /// last hour of the contests that just closed still goes out
+    pub fn flush<R: ExportRow>(&self, now: i64) -> Result<Vec<String>, Box<dyn Error>> {
+        if !self.enabled() {
+            return Ok(Vec::new());
+        }
+
+        self.roll::<R>(now)
+    }
+
+    /// Add the rows of a tick to the staging files of its hour
+    pub fn stage<R: ExportRow>(&self, tick: i64, rows: &[R]) -> Result<(), Box<dyn Error>> {
+        let hour = hour(tick);
+
+        // one write per contest, so a crash can only ever cut off the end of the last line
+        let mut pages: BTreeMap<&str, Vec<u8>> = BTreeMap::new();
+        for row in rows {
+            let lines = pages.entry(row.page()).or_default();
+            serde_json::to_writer(&mut *lines, row)?;
+            lines.push(b'\n');
+        }
+
+        for (page, lines) in pages {
+            let path = self.dir.path(&format!("{}/{}/{}/{}.ndjson", STAGING_DIR, R::KIND, page, hour));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            OpenOptions::new().create(true).append(true).open(&path)?.write_all(&lines)?;
+        }
+
+        Ok(())
+    }
+
+    /// Roll every staged hour before the one `now` is in
+    pub fn roll<R: ExportRow>(&self, now: i64) -> Result<Vec<String>, Box<dyn Error>> {
+        let current = hour(now);
+        let staging = self.dir.path(&format!("{}/{}", STAGING_DIR, R::KIND));
+
+        let pages = match fs::read_dir(&staging) {
+            Ok(pages) => pages,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut rolled = Vec::new();
+        for page in pages {
+            let page = page?;
+            let page_name = page.file_name().to_string_lossy().to_string();
+
+            for staged in fs::read_dir(page.path())? {
+                let staged = staged?.path();
+                let hour = match staged.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_suffix(".ndjson")) {
+                    Some(hour) if hour < current.as_str() => hour.to_string(),
+                    _ => continue,
+                };
+
+                let rows: Vec<R> = read_staged(&staged)?;
+                rolled.extend(self.write_hour(&page_name, &hour, &rows)?);
+
+                // only once everything for the hour is written and queued, otherwise it all happens again next tick
+                fs::remove_file(&staged)?;
+            }
+            // the contest has nothing left to roll, this fails if it has and that's fine
+            let _ = fs::remove_dir(page.path());
+        }
+
+        if !rolled.is_empty() {
+            let schema = self.dir.write(&format!("{}/schema.json", R::KIND), &schema_json(R::COLUMNS)?)?;
+            self.queue(&schema.name)?;
+        }
+
+        Ok(rolled)
+    }
+
+    // write the export files of one contest and hour, `hour` is like 2022-10-01T13
+    fn write_hour<R: ExportRow>(&self, page: &str, hour: &str, rows: &[R]) -> Result<Vec<String>, Box<dyn Error>> {
+        let date = hour.split('T').next().unwrap_or(hour);
+
+        let mut written = Vec::new();
+        for format in self.formats.iter() {
+            let contents = match format {
+                ExportFormat::Ndjson => ndjson_gz(rows)?,
+                ExportFormat::Parquet => parquet(rows)?,
+            };
+
+            let name = format!("{kind}/page={page}/date={date}/{kind}-{hour}.{ext}", kind = R::KIND, page = page, date = date, hour = hour, ext = format.extension());
+            let file = self.dir.write(&name, &contents)?;
+            self.queue(&file.name)?;
+
+            info!("exported file; file={}; rows={}; bytes={}", file.name, rows.len(), file.bytes);
+            written.push(file.name);
+        }
+
+        Ok(written)
+    }
+
+    fn queue(&self, name: &str) -> io::Result<()> {
+        match &self.spool {
+            Some(spool) => spool.enqueue(&format!("export/{}", name), &fs::read(self.dir.path(name))?),
+            None => Ok(()),
+        }
+    }
+}
+
+// the hour `timestamp` is in, like 2022-10-01T13, which sorts the same way the hours go
+fn hour(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0).unwrap_or_default().format("%Y-%m-%dT%H").to_string()
+}
+
+// the rows of a staging file, a line cut off by a crash gets skipped
+fn read_staged<R: ExportRow>(path: &std::path::Path) -> Result<Vec<R>, Box<dyn Error>> {
+    let mut rows = Vec::new();
+    for line in io::BufReader::new(fs::File::open(path)?).lines() {
+        match serde_json::from_str(&line?) {
+            Ok(row) => rows.push(row),
+            Err(e) => warn!("Skipping broken staged row; file={}; error={}", path.display(), e),
+        }
+    }
+
+    Ok(rows)
+}
+
+/// The columns as a bigquery schema
+pub fn schema_json(columns: &[Column]) -> Result<Vec<u8>, serde_json::Error> {
+    let fields: Vec<_> = columns
+        .iter()
+        .map(|column| {
+            let mode = if column.nullable { "NULLABLE" } else { "REQUIRED" };
+            json!({ "name": column.name, "type": column.kind.name(), "mode": mode })
+        })
+        .collect();
+
+    serde_json::to_vec_pretty(&fields)
+}
+
+/// One json object per line with exactly the columns of the schema, gzipped
+pub fn ndjson_gz<R: ExportRow>(rows: &[R]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+
+    for row in rows {
+        let object: Map<_, _> = R::COLUMNS
+            .iter()
+            .zip(row.values())
+            .map(|(column, value)| {
+                let value = match value {
+                    Value::String(value) => json!(value),
+                    Value::Int64(value) => json!(value),
+                    Value::Float64(value) => json!(value),
+                    Value::Null => serde_json::Value::Null,
+                };
+                (column.name.to_string(), value)
+            })
+            .collect();
+
+        serde_json::to_writer(&mut gz, &object)?;
+        gz.write_all(b"\n")?;
+    }
+
+    Ok(gz.finish()?)
+}
+
+/// The rows as a parquet file with one snappy compressed row group
+pub fn parquet<R: ExportRow>(rows: &[R]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let fields = R::COLUMNS
+        .iter()
+        .map(|column| {
+            let builder = match column.kind {
+                ColumnType::String => Type::primitive_type_builder(column.name, PhysicalType::BYTE_ARRAY).with_converted_type(ConvertedType::UTF8),
+                ColumnType::Int64 => Type::primitive_type_builder(column.name, PhysicalType::INT64),
+                ColumnType::Float64 => Type::primitive_type_builder(column.name, PhysicalType::DOUBLE),
+            };
+            let repetition = if column.nullable { Repetition::OPTIONAL } else { Repetition::REQUIRED };
+            Ok(Arc::new(builder.with_repetition(repetition).build()?))
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    let schema = Type::group_type_builder(&R::KIND.replace('-', "_")).with_fields(fields).build()?;
+
+    let properties = WriterProperties::builder().set_compression(ParquetCompression::SNAPPY).build();
+    let mut writer = SerializedFileWriter::new(Vec::new(), Arc::new(schema), Arc::new(properties))?;
+
+    let values: Vec<Vec<Value>> = rows.iter().map(ExportRow::values).collect();
+    let mut row_group = writer.next_row_group()?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        // the nulls are left out of the values and marked in the definition levels instead
+        let cells = values.iter().map(|row| &row[index]).filter(|cell| **cell != Value::Null);
+        let levels: Option<Vec<i16>> = if R::COLUMNS[index].nullable {
+            Some(values.iter().map(|row| i16::from(row[index] != Value::Null)).collect())
+        } else {
+            None
+        };
+
+        match R::COLUMNS[index].kind {
+            ColumnType::String => {
+                let cells: Vec<ByteArray> = cells.map(|cell| ByteArray::from(string(cell).as_bytes())).collect();
+                column.typed::<ByteArrayType>().write_batch(&cells, levels.as_deref(), None)?;
+            }
+            ColumnType::Int64 => {
+                let cells: Vec<i64> = cells.map(|cell| if let Value::Int64(value) = cell { *value } else { 0 }).collect();
+                column.typed::<Int64Type>().write_batch(&cells, levels.as_deref(), None)?;
+            }
+            ColumnType::Float64 => {
+                let cells: Vec<f64> = cells.map(|cell| if let Value::Float64(value) = cell { *value } else { 0.0 }).collect();
+                column.typed::<DoubleType>().write_batch(&cells, levels.as_deref(), None)?;
+            }
+        }
+        column.close()?;
+        index += 1;
+    }
+    row_group.close()?;
+
+    Ok(writer.into_inner()?)
+}
+
+fn string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Int64(value) => value.to_string(),
+        Value::Float64(value) => value.to_string(),
+        Value::Null => String::new(),
+    }
+}
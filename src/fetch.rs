@@ -0,0 +1,255 @@
+//! Fetches pages for the crawlers while keeping us from hammering gogophotocontest.com,
+//! every request waits for a slot on its host and a token from a global token bucket.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use reqwest::{Client, RequestBuilder};
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+use log::{debug, info, warn};
+
+use crate::config::parsed_setting;
+
+// how long to wait before the first retry of a failed contest
+const RETRY_BACKOFF: Duration = Duration::from_secs(2);
+
+/// How hard a crawler is allowed to go at the site, the limits are per crawler
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CrawlLimits {
+    // how many contests, and how many entries within a contest, get crawled at the same time
+    pub concurrency: usize,
+    // how many requests can be in flight to a single host
+    pub per_host: usize,
+    // how many requests we start per second on average, 0 for no limit
+    pub requests_per_second: f64,
+    // how many requests can be started back to back before the rate limit kicks in
+    pub burst: usize,
+    // how many more times a contest gets tried after it fails in a tick
+    pub retries: u32,
+}
+
+impl Default for CrawlLimits {
+    fn default() -> CrawlLimits {
+        CrawlLimits {
+            concurrency: 4,
+            per_host: 4,
+            requests_per_second: 4.0,
+            burst: 8,
+            retries: 2,
+        }
+    }
+}
+
+impl CrawlLimits {
+    /// Read the limits from `--crawl-concurrency`, `--per-host-limit`, `--rate-limit`, `--rate-burst` and
+    /// `--crawl-retries` (or `CRAWL_CONCURRENCY`, `PER_HOST_LIMIT`, `RATE_LIMIT`, `RATE_BURST` and `CRAWL_RETRIES`)
+    pub fn from_settings() -> CrawlLimits {
+        let defaults = CrawlLimits::default();
+
+        CrawlLimits {
+            concurrency: parsed_setting("--crawl-concurrency", "CRAWL_CONCURRENCY").unwrap_or(defaults.concurrency).max(1),
+            per_host: parsed_setting("--per-host-limit", "PER_HOST_LIMIT").unwrap_or(defaults.per_host).max(1),
+            requests_per_second: parsed_setting("--rate-limit", "RATE_LIMIT").unwrap_or(defaults.requests_per_second).max(0.0),
+            burst: parsed_setting("--rate-burst", "RATE_BURST").unwrap_or(defaults.burst).max(1),
+            retries: parsed_setting("--crawl-retries", "CRAWL_RETRIES").unwrap_or(defaults.retries),
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Counters for one tick of a crawler
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct FetchStats {
+    pub requests: u64,
+    pub failed_requests: u64,
+    // how long the requests spent waiting on the limits before being sent
+    pub waited_ms: u64,
+}
+
+#[derive(Clone)]
+pub struct Fetcher {
+    client: Client,
+    limits: CrawlLimits,
+    bucket: Arc<tokio::sync::Mutex<TokenBucket>>,
+    hosts: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+    requests: Arc<AtomicU64>,
+    failed_requests: Arc<AtomicU64>,
+    waited_micros: Arc<AtomicU64>,
+}
+
+impl Fetcher {
+    pub fn new(limits: CrawlLimits, timeout: Duration) -> Result<Fetcher, reqwest::Error> {
+        let client = reqwest::ClientBuilder::new()
+            .timeout(timeout)
+            .build()?;
+
+        Ok(Fetcher {
+            client,
+            limits,
+            bucket: Arc::new(tokio::sync::Mutex::new(TokenBucket {
+                tokens: limits.burst as f64,
+                last_refill: Instant::now(),
+            })),
+            hosts: Arc::new(Mutex::new(HashMap::new())),
+            requests: Arc::new(AtomicU64::new(0)),
+            failed_requests: Arc::new(AtomicU64::new(0)),
+            waited_micros: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    pub fn limits(&self) -> CrawlLimits {
+        self.limits
+    }
+
+    /// GET a page and hand back its body
+    pub async fn get_text(&self, url: &str) -> Result<String, reqwest::Error> {
+        self.send(url, self.client.get(url)).await
+    }
+
+    /// POST a form to a page and hand back its body, this is how the postbacks get sent
+    pub async fn post_form(&self, url: &str, fields: &[(String, String)]) -> Result<String, reqwest::Error> {
+        self.send(url, self.client.post(url).form(fields)).await
+    }
+
+    async fn send(&self, url: &str, request: RequestBuilder) -> Result<String, reqwest::Error> {
+        let waiting = Instant::now();
+        let host = self.host_semaphore(url);
+        let _permit = host.acquire().await.expect("host semaphores are never closed");
+        self.take_token().await;
+        self.waited_micros.fetch_add(waiting.elapsed().as_micros() as u64, Ordering::Relaxed);
+
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let result = async { request.send().await?.error_for_status()?.text().await }.await;
+        if result.is_err() {
+            self.failed_requests.fetch_add(1, Ordering::Relaxed);
+        }
+
+        result
+    }
+
+    /// Keep calling `attempt` until it works or we run out of retries, doubling the wait between
+    /// each try. Hands back how many attempts it took along with the last result.
+    pub async fn with_retries<T, E, F, Fut>(&self, what: &str, mut attempt: F) -> (u32, Result<T, E>)
+    where
+        E: Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut backoff = RETRY_BACKOFF;
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+            match attempt().await {
+                Err(e) if attempts <= self.limits.retries => {
+                    warn!("Crawl failed, retrying; what={}; attempt={}; backoff={:?}; error={}", what, attempts, backoff, e);
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                result => return (attempts, result),
+            }
+        }
+    }
+
+    /// The counters since the last time this was called
+    pub fn take_stats(&self) -> FetchStats {
+        FetchStats {
+            requests: self.requests.swap(0, Ordering::Relaxed),
+            failed_requests: self.failed_requests.swap(0, Ordering::Relaxed),
+            waited_ms: self.waited_micros.swap(0, Ordering::Relaxed) / 1000,
+        }
+    }
+
+    fn host_semaphore(&self, url: &str) -> Arc<Semaphore> {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .unwrap_or_default();
+
+        self.hosts
+            .lock()
+            .unwrap()
+            .entry(host)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.limits.per_host)))
+            .clone()
+    }
+
+    async fn take_token(&self) {
+        if self.limits.requests_per_second <= 0.0 {
+            return;
+        }
+
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+
+                let now = Instant::now();
+                let refill = now.duration_since(bucket.last_refill).as_secs_f64() * self.limits.requests_per_second;
+                bucket.tokens = (bucket.tokens + refill).min(self.limits.burst as f64);
+                bucket.last_refill = now;
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.limits.requests_per_second)
+            };
+
+            debug!("waiting on the rate limit; wait={:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// How long one contest took to crawl
+#[derive(Debug, Clone, Serialize)]
+pub struct ContestTiming {
+    pub page: String,
+    pub duration_ms: u64,
+    // how many dogs (or contests) came back from it
+    pub results: usize,
+    pub attempts: u32,
+    pub ok: bool,
+}
+
+/// Timings for a whole tick of a crawler so the limits can be tuned
+#[derive(Debug, Clone, Serialize)]
+pub struct TickMetrics {
+    pub timestamp: i64,
+    pub duration_ms: u64,
+    pub limits: CrawlLimits,
+    pub fetch: FetchStats,
+    pub contests: Vec<ContestTiming>,
+}
+
+impl TickMetrics {
+    pub fn log(&self, crawler: &str) {
+        let slowest = self.contests.iter().max_by_key(|c| c.duration_ms);
+
+        info!(
+            "tick metrics; crawler={}; duration_ms={}; requests={}; failed_requests={}; waited_ms={}; slowest={}; slowest_ms={}",
+            crawler,
+            self.duration_ms,
+            self.fetch.requests,
+            self.fetch.failed_requests,
+            self.fetch.waited_ms,
+            slowest.map_or("", |c| c.page.as_str()),
+            slowest.map_or(0, |c| c.duration_ms),
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Where the fundraising of a contest is headed: how many dollars an hour have been coming in
+//! over the last `--forecast-window-hours` (or `FORECAST_WINDOW_HOURS`, a day by default) of
+//! crawls, what the total ends up at if it keeps coming in that fast until the contest ends,
+//! and how fast it has to come in from here to make the goal.
+//!
+//! The end comes from `ends_at` in the contests file, without it there is only the pace.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config, history::ContestRecord, Money};
+
+pub const DEFAULT_WINDOW_HOURS: i64 = 24;
+
+// with the crawls any closer together than this the pace is mostly noise
+const MIN_SPAN_SECONDS: i64 = 15 * 60;
+
+const HOUR_SECONDS: f64 = 60.0 * 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForecastSettings {
+    // how far back the pace is measured
+    pub window_hours: i64,
+}
+
+impl Default for ForecastSettings {
+    fn default() -> ForecastSettings {
+        ForecastSettings {
+            window_hours: DEFAULT_WINDOW_HOURS,
+        }
+    }
+}
+
+impl ForecastSettings {
+    /// The window from `--forecast-window-hours` or `FORECAST_WINDOW_HOURS`
+    pub fn from_settings() -> ForecastSettings {
+        ForecastSettings {
+            window_hours: config::parsed_setting("--forecast-window-hours", "FORECAST_WINDOW_HOURS")
+                .filter(|hours| *hours > 0)
+                .unwrap_or(DEFAULT_WINDOW_HOURS),
+        }
+    }
+
+    pub fn window_seconds(&self) -> i64 {
+        self.window_hours * 60 * 60
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Forecast {
+    // dollars an hour over the window, None until there are two crawls far enough apart
+    pub velocity_per_hour: Option<Money>,
+    // when the contest ends, None if the contests file doesn't say
+    pub ends_at: Option<i64>,
+    // where the total ends up if the money keeps coming in at `velocity_per_hour`,
+    // once the contest is over it is just the total
+    pub projected_total: Option<Money>,
+    // dollars an hour still needed to make the goal by the end, zero once it is made
+    pub required_per_hour: Option<Money>,
+    // whether `projected_total` makes the goal
+    pub on_track: Option<bool>,
+}
+
+/// Forecast a contest from the total it had raised at each crawl, champ day money included,
+/// oldest first and already cut down to the window. `now` is when the hours left get counted from.
+pub fn forecast(points: &[(i64, Money)], goal: Money, ends_at: Option<i64>, now: i64) -> Forecast {
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => {
+            return Forecast {
+                ends_at,
+                ..Forecast::default()
+            }
+        }
+    };
+    let raised = last.1;
+
+    let span = last.0 - first.0;
+    // the totals only go down when gogophoto corrects something, that is not a pace to project
+    let velocity = if span >= MIN_SPAN_SECONDS {
+        Some(Money::from_cents(((last.1 - first.1).cents().max(0) as f64 * HOUR_SECONDS / span as f64).round() as i64))
+    } else {
+        None
+    };
+
+    let (projected_total, required_per_hour) = match ends_at {
+        Some(ends_at) if ends_at > now => {
+            let hours_left = (ends_at - now) as f64 / HOUR_SECONDS;
+            let projected = velocity.map(|velocity| raised + Money::from_cents((velocity.cents() as f64 * hours_left).round() as i64));
+            let missing = (goal - raised).cents().max(0);
+            (projected, Some(Money::from_cents((missing as f64 / hours_left).ceil() as i64)))
+        }
+        // it is over, whatever it has is what it ends up with
+        Some(_) => (Some(raised), None),
+        None => (None, None),
+    };
+
+    Forecast {
+        velocity_per_hour: velocity,
+        ends_at,
+        projected_total,
+        required_per_hour,
+        on_track: projected_total.map(|projected| projected >= goal),
+    }
+}
+
+/// The totals the contest had raised at each of the crawls, for [`forecast`]
+pub fn totals(records: &[ContestRecord]) -> Vec<(i64, Money)> {
+    records.iter().map(|record| (record.timestamp, record.raised + record.champ_day)).collect()
+}
@@ -0,0 +1,508 @@
+//! Every crawl gets appended to a local sqlite database so we can look at how
+//! the votes and the money moved over time and rebuild any past leaderboard,
+//! even if the csv files in the cloud bucket are gone.
+
+use std::{collections::HashSet, path::Path, time::Duration};
+
+use rusqlite::{params, Connection, OptionalExtension, Row, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    events::{Event, EventKind},
+    ContestData, EntryData, Money,
+};
+
+pub use rusqlite::Error;
+
+pub const DEFAULT_HISTORY_DB: &str = "history.sqlite";
+
+// Each migration moves the database up one version, the version the database is at
+// is kept in `PRAGMA user_version`. Never change a migration that already shipped,
+// add a new one to the end instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: crawls with the entry and contest snapshots they captured
+    "CREATE TABLE crawls (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind TEXT NOT NULL,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE INDEX crawls_kind_timestamp ON crawls (kind, timestamp);
+
+    CREATE TABLE entry_snapshots (
+        crawl_id INTEGER NOT NULL REFERENCES crawls (id),
+        contest_page TEXT NOT NULL,
+        display_name TEXT NOT NULL,
+        entry_id TEXT NOT NULL,
+        entry_url TEXT NOT NULL,
+        dog TEXT NOT NULL,
+        category TEXT NOT NULL,
+        picture TEXT NOT NULL,
+        votes INTEGER NOT NULL,
+        raised INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        PRIMARY KEY (contest_page, entry_url, timestamp)
+    );
+    CREATE INDEX entry_snapshots_entry ON entry_snapshots (entry_id, timestamp);
+    CREATE INDEX entry_snapshots_crawl ON entry_snapshots (crawl_id);
+
+    CREATE TABLE contest_snapshots (
+        crawl_id INTEGER NOT NULL REFERENCES crawls (id),
+        contest_page TEXT NOT NULL,
+        display_name TEXT NOT NULL,
+        goal INTEGER NOT NULL,
+        raised INTEGER NOT NULL,
+        total_entries INTEGER NOT NULL,
+        champ_day INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        PRIMARY KEY (contest_page, timestamp)
+    );
+    CREATE INDEX contest_snapshots_crawl ON contest_snapshots (crawl_id);",
+    // 2: money is kept in cents instead of whole dollars
+    "UPDATE entry_snapshots SET raised = raised * 100;
+    UPDATE contest_snapshots SET goal = goal * 100, raised = raised * 100, champ_day = champ_day * 100;",
+    // 3: what happened to the dogs between crawls, see `events`
+    "CREATE TABLE events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        kind TEXT NOT NULL,
+        contest_page TEXT NOT NULL,
+        entry_id TEXT NOT NULL,
+        dog TEXT NOT NULL,
+        previous_rank INTEGER,
+        rank INTEGER,
+        previous_votes INTEGER,
+        votes INTEGER NOT NULL,
+        other_entry_id TEXT,
+        other_dog TEXT,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE INDEX events_timestamp ON events (timestamp);
+    CREATE INDEX events_contest_timestamp ON events (contest_page, timestamp);",
+    // 4: the milestones that have been sent out, so they only ever go out once
+    "CREATE TABLE notifications (
+        key TEXT PRIMARY KEY,
+        timestamp INTEGER NOT NULL
+    );",
+    // 5: the last crawl each crawler took of a contest once it closed, see `schedule`
+    "CREATE TABLE closing_crawls (
+        kind TEXT NOT NULL,
+        contest_page TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        PRIMARY KEY (kind, contest_page)
+    );",
+];
+
+/// How far apart the points of a time series should be, only the last
+/// snapshot in each minute/hour/day is kept
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Downsample {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Downsample {
+    pub fn seconds(self) -> i64 {
+        match self {
+            Downsample::Minute => 60,
+            Downsample::Hour => 60 * 60,
+            Downsample::Day => 24 * 60 * 60,
+        }
+    }
+}
+
+// sqlite hands back the other columns of the row with the max timestamp when a query
+// uses MAX(), so grouping by the bucket gives us the last snapshot in each bucket.
+// Without downsampling every snapshot gets a bucket of its own.
+fn bucket_seconds(downsample: Option<Downsample>) -> i64 {
+    downsample.map_or(1, Downsample::seconds)
+}
+
+/// A single dog at a single point in time
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct EntryRecord {
+    pub contest_page: String,
+    pub display_name: String,
+    pub entry_id: String,
+    pub entry_url: String,
+    pub dog: String,
+    pub category: String,
+    pub picture: String,
+    pub votes: usize,
+    pub raised: Money,
+    pub timestamp: i64,
+}
+
+impl EntryRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<EntryRecord> {
+        Ok(EntryRecord {
+            contest_page: row.get("contest_page")?,
+            display_name: row.get("display_name")?,
+            entry_id: row.get("entry_id")?,
+            entry_url: row.get("entry_url")?,
+            dog: row.get("dog")?,
+            category: row.get("category")?,
+            picture: row.get("picture")?,
+            votes: row.get::<_, i64>("votes")? as usize,
+            raised: row.get("raised")?,
+            timestamp: row.get("timestamp")?,
+        })
+    }
+}
+
+/// A single contest at a single point in time
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct ContestRecord {
+    pub contest_page: String,
+    pub display_name: String,
+    pub goal: Money,
+    pub raised: Money,
+    pub total_entries: usize,
+    pub champ_day: Money,
+    pub timestamp: i64,
+}
+
+impl ContestRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<ContestRecord> {
+        Ok(ContestRecord {
+            contest_page: row.get("contest_page")?,
+            display_name: row.get("display_name")?,
+            goal: row.get("goal")?,
+            raised: row.get("raised")?,
+            total_entries: row.get::<_, i64>("total_entries")? as usize,
+            champ_day: row.get("champ_day")?,
+            timestamp: row.get("timestamp")?,
+        })
+    }
+}
+
+fn event_from_row(row: &Row) -> rusqlite::Result<Event> {
+    let kind: String = row.get("kind")?;
+    let usize_column = |name: &str| -> rusqlite::Result<Option<usize>> { Ok(row.get::<_, Option<i64>>(name)?.map(|value| value as usize)) };
+
+    Ok(Event {
+        kind: EventKind::from_name(&kind).ok_or_else(|| rusqlite::Error::InvalidColumnType(0, kind.clone(), rusqlite::types::Type::Text))?,
+        contest: row.get("contest_page")?,
+        entry_id: row.get("entry_id")?,
+        dog: row.get("dog")?,
+        previous_rank: usize_column("previous_rank")?,
+        rank: usize_column("rank")?,
+        previous_votes: usize_column("previous_votes")?,
+        votes: row.get::<_, i64>("votes")? as usize,
+        other_entry_id: row.get("other_entry_id")?,
+        other_dog: row.get("other_dog")?,
+        timestamp: row.get("timestamp")?,
+    })
+}
+
+/// Which events to read back, newest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventFilter {
+    // unix timestamps, both ends are inclusive
+    pub from: i64,
+    pub to: i64,
+    pub contest: Option<String>,
+    pub kind: Option<EventKind>,
+    pub limit: usize,
+}
+
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// Open the history database given with `--history-db` or the
+    /// `HISTORY_DB` environment variable, defaulting to `history.sqlite`
+    pub fn open_default() -> Result<History, Error> {
+        History::open(History::default_path())
+    }
+
+    /// Where `open_default` opens the database
+    pub fn default_path() -> String {
+        config::setting("--history-db", "HISTORY_DB").unwrap_or_else(|| DEFAULT_HISTORY_DB.into())
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<History, Error> {
+        let conn = Connection::open(path)?;
+
+        // both crawlers and the api use the same database, so let readers and
+        // the writers get out of each other's way instead of failing right away
+        conn.busy_timeout(Duration::from_secs(10))?;
+        conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+
+        History::from_connection(conn)
+    }
+
+    pub fn open_in_memory() -> Result<History, Error> {
+        History::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<History, Error> {
+        let mut history = History { conn };
+        history.migrate()?;
+
+        Ok(history)
+    }
+
+    fn migrate(&mut self) -> Result<(), Error> {
+        // every task opens the database at once when the crawler starts, so take the write
+        // lock before looking at the version or two of them run the same migration
+        let tx = self.conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let version: usize = tx.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))? as usize;
+
+        for (idx, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            tx.execute_batch(migration)?;
+            tx.execute_batch(&format!("PRAGMA user_version = {}", idx + 1))?;
+        }
+
+        tx.commit()
+    }
+
+    fn start_crawl(tx: &rusqlite::Transaction, kind: &str, timestamp: i64) -> Result<i64, Error> {
+        tx.execute("INSERT INTO crawls (kind, timestamp) VALUES (?1, ?2)", params![kind, timestamp])?;
+
+        Ok(tx.last_insert_rowid())
+    }
+
+    /// Save all the dogs from one crawl, returns the id of the crawl
+    pub fn record_entries(&mut self, timestamp: i64, entries: &[EntryData]) -> Result<i64, Error> {
+        let tx = self.conn.transaction()?;
+        let crawl_id = History::start_crawl(&tx, "dogs", timestamp)?;
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO entry_snapshots
+                    (crawl_id, contest_page, display_name, entry_id, entry_url, dog, category, picture, votes, raised, timestamp)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            )?;
+
+            for entry in entries {
+                stmt.execute(params![
+                    crawl_id,
+                    entry.contest.page,
+                    entry.contest.display_name,
+                    entry.entry_id(),
+                    entry.page,
+                    entry.dog,
+                    entry.category,
+                    entry.picture,
+                    entry.votes as i64,
+                    entry.raised,
+                    entry.timestamp,
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(crawl_id)
+    }
+
+    /// Save all the contest totals from one crawl, returns the id of the crawl
+    pub fn record_contests(&mut self, timestamp: i64, contests: &[ContestData]) -> Result<i64, Error> {
+        let tx = self.conn.transaction()?;
+        let crawl_id = History::start_crawl(&tx, "goals", timestamp)?;
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO contest_snapshots
+                    (crawl_id, contest_page, display_name, goal, raised, total_entries, champ_day, timestamp)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+
+            for data in contests {
+                stmt.execute(params![
+                    crawl_id,
+                    data.contest.page,
+                    data.contest.display_name,
+                    data.goal,
+                    data.raised,
+                    data.total_entries as i64,
+                    data.champ_day,
+                    data.timestamp,
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(crawl_id)
+    }
+
+    /// Save the events noticed in one crawl
+    pub fn record_events(&mut self, events: &[Event]) -> Result<(), Error> {
+        let tx = self.conn.transaction()?;
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO events
+                    (kind, contest_page, entry_id, dog, previous_rank, rank, previous_votes, votes, other_entry_id, other_dog, timestamp)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            )?;
+
+            for event in events {
+                stmt.execute(params![
+                    event.kind.name(),
+                    event.contest,
+                    event.entry_id,
+                    event.dog,
+                    event.previous_rank.map(|rank| rank as i64),
+                    event.rank.map(|rank| rank as i64),
+                    event.previous_votes.map(|votes| votes as i64),
+                    event.votes as i64,
+                    event.other_entry_id,
+                    event.other_dog,
+                    event.timestamp,
+                ])?;
+            }
+        }
+
+        tx.commit()
+    }
+
+    /// The events that match `filter`, newest first
+    pub fn events(&self, filter: &EventFilter) -> Result<Vec<Event>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT * FROM events
+            WHERE timestamp BETWEEN ?1 AND ?2
+                AND (?3 IS NULL OR contest_page = ?3)
+                AND (?4 IS NULL OR kind = ?4)
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?5",
+        )?;
+
+        let rows = stmt.query_map(
+            params![filter.from, filter.to, filter.contest, filter.kind.map(EventKind::name), filter.limit as i64],
+            event_from_row,
+        )?;
+        rows.collect()
+    }
+
+    /// Remember that the milestone `key` is going out, false if it already has
+    pub fn claim_notification(&mut self, key: &str, timestamp: i64) -> Result<bool, Error> {
+        let claimed = self.conn.execute(
+            "INSERT OR IGNORE INTO notifications (key, timestamp) VALUES (?1, ?2)",
+            params![key, timestamp],
+        )?;
+
+        Ok(claimed > 0)
+    }
+
+    /// Forget a milestone that couldn't be sent, so it gets another go
+    pub fn release_notification(&mut self, key: &str) -> Result<(), Error> {
+        self.conn.execute("DELETE FROM notifications WHERE key = ?1", params![key])?;
+
+        Ok(())
+    }
+
+    /// Forget every milestone starting with `prefix` but `key`, so they can go out again
+    pub fn release_notifications_except(&mut self, prefix: &str, key: &str) -> Result<(), Error> {
+        self.conn.execute(
+            "DELETE FROM notifications WHERE substr(key, 1, length(?1)) = ?1 AND key != ?2",
+            params![prefix, key],
+        )?;
+
+        Ok(())
+    }
+
+    /// Remember that the `kind` crawler took its closing crawl of a contest
+    pub fn record_closing_crawl(&mut self, kind: &str, contest_page: &str, timestamp: i64) -> Result<(), Error> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO closing_crawls (kind, contest_page, timestamp) VALUES (?1, ?2, ?3)",
+            params![kind, contest_page, timestamp],
+        )?;
+
+        Ok(())
+    }
+
+    /// The pages of the contests the `kind` crawler took its closing crawl of
+    pub fn closing_crawls(&self, kind: &str) -> Result<HashSet<String>, Error> {
+        let mut stmt = self.conn.prepare("SELECT contest_page FROM closing_crawls WHERE kind = ?1")?;
+
+        let rows = stmt.query_map(params![kind], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// The snapshots we have of a single dog in a contest between `from` and `to` (inclusive),
+    /// entry ids only mean something within their contest so the page is needed too
+    pub fn entry_history(
+        &self,
+        contest_page: &str,
+        entry_id: &str,
+        from: i64,
+        to: i64,
+        downsample: Option<Downsample>,
+    ) -> Result<Vec<EntryRecord>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT *, MAX(timestamp) FROM entry_snapshots
+            WHERE contest_page = ?1 AND entry_id = ?2 AND timestamp BETWEEN ?3 AND ?4
+            GROUP BY timestamp / ?5
+            ORDER BY timestamp",
+        )?;
+
+        let rows = stmt.query_map(params![contest_page, entry_id, from, to, bucket_seconds(downsample)], EntryRecord::from_row)?;
+        rows.collect()
+    }
+
+    /// The page of the contest a dog was last crawled in, for when it isn't said which one
+    pub fn latest_entry_contest(&self, entry_id: &str) -> Result<Option<String>, Error> {
+        self.conn.query_row(
+            "SELECT contest_page FROM entry_snapshots WHERE entry_id = ?1 ORDER BY timestamp DESC LIMIT 1",
+            params![entry_id],
+            |row| row.get(0),
+        ).optional()
+    }
+
+    /// The snapshots we have of a single contest between `from` and `to` (inclusive)
+    pub fn contest_history(&self, contest_page: &str, from: i64, to: i64, downsample: Option<Downsample>) -> Result<Vec<ContestRecord>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT *, MAX(timestamp) FROM contest_snapshots
+            WHERE contest_page = ?1 AND timestamp BETWEEN ?2 AND ?3
+            GROUP BY timestamp / ?4
+            ORDER BY timestamp",
+        )?;
+
+        let rows = stmt.query_map(params![contest_page, from, to, bucket_seconds(downsample)], ContestRecord::from_row)?;
+        rows.collect()
+    }
+
+    /// The last snapshot we have of a contest, which for a contest that closed is how it ended
+    pub fn latest_contest(&self, contest_page: &str) -> Result<Option<ContestRecord>, Error> {
+        self.conn.query_row(
+            "SELECT * FROM contest_snapshots WHERE contest_page = ?1 ORDER BY timestamp DESC LIMIT 1",
+            params![contest_page],
+            ContestRecord::from_row,
+        ).optional()
+    }
+
+    /// The dogs of a contest as they were in the last crawl that had it, most votes first
+    pub fn latest_entries(&self, contest_page: &str) -> Result<Vec<EntryRecord>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT * FROM entry_snapshots
+            WHERE contest_page = ?1 AND crawl_id = (SELECT MAX(crawl_id) FROM entry_snapshots WHERE contest_page = ?1)
+            ORDER BY votes DESC, dog",
+        )?;
+
+        let rows = stmt.query_map(params![contest_page], EntryRecord::from_row)?;
+        rows.collect()
+    }
+
+    /// Rebuild the leaderboard as it was at `at` from the last snapshot of every dog at or before
+    /// it, sorted by votes like `top-dogs.json`. Not every crawl has every contest in it, the ones
+    /// that failed or weren't open only show up in the crawls before.
+    pub fn leaderboard_at(&self, at: i64) -> Result<Vec<EntryRecord>, Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT entry_snapshots.* FROM entry_snapshots
+            JOIN (
+                SELECT contest_page, entry_url, MAX(timestamp) AS timestamp FROM entry_snapshots
+                WHERE timestamp <= ?1
+                GROUP BY contest_page, entry_url
+            ) latest USING (contest_page, entry_url, timestamp)
+            ORDER BY votes DESC, dog",
+        )?;
+
+        let rows = stmt.query_map(params![at], EntryRecord::from_row)?;
+        rows.collect()
+    }
+}
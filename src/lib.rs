@@ -1,39 +1,134 @@
+use std::path::Path;
+
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone, Eq, Ord, PartialEq, PartialOrd)]
+pub mod categories;
+pub mod config;
+pub mod events;
+pub mod export;
+pub mod fetch;
+pub mod forecast;
+pub mod history;
+pub mod live;
+pub mod money;
+pub mod notify;
+pub mod output;
+pub mod postback;
+pub mod schedule;
+pub mod scraper;
+pub mod seasons;
+pub mod selectors;
+pub mod sinks;
+pub mod snapshots;
+pub mod spool;
+pub mod stale;
+pub mod tasks;
+
+pub use categories::{CategoryMatch, CategoryReport, MatchKind};
+pub use config::ConfigError;
+pub use forecast::Forecast;
+pub use money::Money;
+pub use schedule::ContestStatus;
+
+// how many dogs make it on to the global leaderboard
+pub const GLOBAL_LEADERBOARD_SIZE: usize = 15;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Contest {
     pub display_name: String,
     pub page: String,
-    pub champ_day: usize,
+    // money raised on champ day, which the site does not count
+    pub champ_day: Money,
     pub num_dogs: usize,
+    // Other names the contest goes by in the entry categories,
+    // e.g. "Misfit Mutts" for the misfit mutts contest
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    // The season the contest is part of, e.g. "Fall 2022"
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub season: String,
+    // The same for a shelter's contest every season, so the seasons can be compared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shelter: Option<String>,
+    // When the contest opens, as unix seconds, it isn't crawled before then
+    #[serde(default, deserialize_with = "config::deserialize_timestamp", skip_serializing_if = "Option::is_none")]
+    pub starts_at: Option<i64>,
+    // When the contest is over, as unix seconds, the forecasts project the totals out to it
+    #[serde(default, deserialize_with = "config::deserialize_timestamp", skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<i64>,
+    // When the voting stops, if that is before the contest ends
+    #[serde(default, deserialize_with = "config::deserialize_timestamp", skip_serializing_if = "Option::is_none")]
+    pub voting_closes_at: Option<i64>,
+}
+
+impl Contest {
+    /// Which shelter the contest is for, the page when the contests file doesn't say
+    pub fn shelter_key(&self) -> &str {
+        self.shelter.as_deref().unwrap_or(&self.page)
+    }
+
+    /// When nothing about the contest changes anymore, so there is nothing left to crawl
+    pub fn closes_at(&self) -> Option<i64> {
+        self.voting_closes_at.or(self.ends_at)
+    }
+
+    /// Whether the contest is open at `now`, contests without dates are always live
+    pub fn status(&self, now: i64) -> ContestStatus {
+        if self.starts_at.is_some_and(|starts_at| now < starts_at) {
+            ContestStatus::Upcoming
+        } else if self.closes_at().is_some_and(|closes_at| now >= closes_at) {
+            ContestStatus::Closed
+        } else {
+            ContestStatus::Live
+        }
+    }
 }
 
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ContestData {
     pub contest: Contest,
-    pub goal: usize,
-    pub raised: usize,
+    pub goal: Money,
+    pub raised: Money,
     // Total entries in the contest
     pub total_entries: usize,
     // this will usually just be a hardcoded thing
-    pub champ_day: usize,
+    pub champ_day: Money,
     // When this data was captured
     pub timestamp: i64,
+    // Set when the contest failed to crawl and this is the last good data we had for it
+    #[serde(default)]
+    pub stale: bool,
+    // How old, in seconds, the data was when it got carried forward
+    #[serde(default)]
+    pub stale_age: i64,
+    // Where the fundraising is headed, from the crawl history
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forecast: Option<Forecast>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct ContestDataCSV {
     pub display_name: String,
     pub page: String,
-    pub goal: usize,
-    pub raised: usize,
+    pub goal: Money,
+    pub raised: Money,
     // Total entries in the contest
     pub total_entries: usize,
     // this will usually just be a hardcoded thing
-    pub champ_day: usize,
+    pub champ_day: Money,
     // When this data was captured
     pub timestamp: i64,
+    // The forecast, these are empty when there is not enough history or no end date.
+    // They default so rows written before they existed still read back
+    #[serde(default)]
+    pub ends_at: Option<i64>,
+    #[serde(default)]
+    pub velocity_per_hour: Option<Money>,
+    #[serde(default)]
+    pub projected_total: Option<Money>,
+    #[serde(default)]
+    pub required_per_hour: Option<Money>,
 }
 
 impl ContestDataCSV {
@@ -46,106 +141,92 @@ impl ContestDataCSV {
             total_entries: data.total_entries,
             champ_day: data.champ_day,
             timestamp: data.timestamp,
+            ends_at: data.contest.ends_at,
+            velocity_per_hour: data.forecast.and_then(|f| f.velocity_per_hour),
+            projected_total: data.forecast.and_then(|f| f.projected_total),
+            required_per_hour: data.forecast.and_then(|f| f.required_per_hour),
         }
     }
 }
 
 
-// Just an empty struct to serve up all the contest
-// that we want to crawl
-pub struct Contests {}
+// All the contests that we want to crawl, loaded from the
+// contest roster file so it can be changed without a rebuild
+#[derive(Debug, Clone)]
+pub struct Contests {
+    contests: Vec<Contest>,
+    // oldest first, the last one is the one being crawled
+    seasons: Vec<String>,
+}
+
 impl Contests {
-    pub fn get_all() -> Vec<Contest> {
-        Vec::from([
-            Contest {
-                display_name: "Lakeshore Humane Society's NEW Top Dog Fall 2022".into(),
-                page: "newtopdoglakeshorefall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            },
-            Contest {
-                display_name: "Misfit Mutts's NEW Top Dog Fall 2022".into(),
-                page: "newtopdogmisfitfall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            },
-            Contest {
-                display_name: "Neenah's NEW Top Dog Fall 2022".into(),
-                page: "newtopdogneenahfall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            },
-            Contest {
-                display_name: "Mit Liebe's NEW Top Dog Fall 2022".into(),
-                page: "newtopdogmitliebefall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            },
-            Contest {
-                display_name: "Oshkosh's NEW Top Dog Fall 2022".into(),
-                page: "newtopdogoahsfall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            },
-            Contest {
-                display_name: "Sandi Paws's NEW Top Dog Fall 2022".into(),
-                page: "newtopdogsandipawsfall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            }
-        ])
-    }
-
-    pub fn from_category(category: &str) -> Option<Contest> {
-        let category = category.to_lowercase();
-
-        let mut ret: Option<Contest> = None;
-
-        if category.contains("lakeshore") {
-            ret = Some(Contest {
-                display_name: "Lakeshore Humane Society's NEW Top Dog Fall 2022".into(),
-                page: "newtopdoglakeshorefall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            });
-        } else if category.contains("misfit mutt") {
-            ret = Some(Contest {
-                display_name: "Misfit Mutts's NEW Top Dog Fall 2022".into(),
-                page: "newtopdogmisfitfall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            });
-        } else if category.contains("neenah") {
-            ret = Some(Contest {
-                display_name: "Neenah's NEW Top Dog Fall 2022".into(),
-                page: "newtopdogneenahfall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            });
-        } else if category.contains("mit liebe") {
-            ret = Some(Contest {
-                display_name: "Mit Liebe's NEW Top Dog Fall 2022".into(),
-                page: "newtopdogmitliebefall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            });
-        } else if category.contains("oshskosh") {
-            ret = Some(Contest {
-                display_name: "Oshkosh's NEW Top Dog Fall 2022".into(),
-                page: "newtopdogoahsfall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            });
-        } else if category.contains("sandi paw") {
-            ret = Some(Contest {
-                display_name: "Sandi Paws's NEW Top Dog Fall 2022".into(),
-                page: "newtopdogsandipawsfall2022".into(),
-                champ_day: 0,
-                num_dogs: 15,
-            });
+    /// Load the contest roster from the file given with `--contests` or the
+    /// `CONTESTS_FILE` environment variable, defaulting to `contests.toml`
+    pub fn load() -> Result<Contests, ConfigError> {
+        let path = config::setting("--contests", "CONTESTS_FILE")
+            .unwrap_or_else(|| config::DEFAULT_CONTESTS_FILE.into());
+
+        Contests::from_path(path)
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Contests, ConfigError> {
+        let roster = config::load_contests(path.as_ref())?;
+
+        Ok(Contests {
+            contests: roster.contests,
+            seasons: roster.seasons,
+        })
+    }
+
+    pub fn get_all(&self) -> Vec<Contest> {
+        self.contests.clone()
+    }
+
+    /// Every season, oldest first
+    pub fn seasons(&self) -> &[String] {
+        &self.seasons
+    }
+
+    /// The newest season, the one the crawlers crawl
+    pub fn current_season(&self) -> &str {
+        self.seasons.last().map_or("", String::as_str)
+    }
+
+    /// The season before `season`, None for the first one
+    pub fn previous_season(&self, season: &str) -> Option<&str> {
+        let index = self.seasons.iter().position(|s| s == season)?;
+
+        index.checked_sub(1).map(|previous| self.seasons[previous].as_str())
+    }
+
+    /// Only the contests of `season`, None if there is no such season
+    pub fn season(&self, season: &str) -> Option<Contests> {
+        if !self.seasons.iter().any(|s| s == season) {
+            return None;
         }
 
-        ret
+        Some(Contests {
+            contests: self.contests.iter().filter(|c| c.season == season).cloned().collect(),
+            seasons: vec![season.to_string()],
+        })
+    }
+
+    /// Only the contests of the current season
+    pub fn current(&self) -> Contests {
+        self.season(self.current_season()).unwrap_or_else(|| self.clone())
+    }
+
+    pub fn from_page(&self, page: &str) -> Option<Contest> {
+        self.contests.iter().find(|c| c.page == page).cloned()
+    }
+
+    /// Find the contest that an entry category belongs to, see [`categories::match_category`]
+    pub fn match_category(&self, category: &str) -> Option<CategoryMatch> {
+        categories::match_category(&self.contests, category)
+    }
+
+    pub fn from_category(&self, category: &str) -> Option<Contest> {
+        self.match_category(category).map(|found| found.contest)
     }
 }
 
@@ -158,7 +239,7 @@ pub struct EntryData {
     pub votes: usize,
     // votes are not 1:1 with money so
     // we need to have a raised value to encode that
-    pub raised: usize,
+    pub raised: Money,
     // Which contest the dog belongs to
     pub contest: Contest,
     // Which category the dog belongs to
@@ -169,9 +250,26 @@ pub struct EntryData {
     pub picture: String,
     // When was this data captured
     pub timestamp: i64,
+    // Set when the contest failed to crawl and this is the last good data we had for it
+    #[serde(default)]
+    pub stale: bool,
+    // How old, in seconds, the data was when it got carried forward
+    #[serde(default)]
+    pub stale_age: i64,
 }
 
 
+impl EntryData {
+    /// The gogophoto id of the entry, which is the last part of the entry page url
+    pub fn entry_id(&self) -> &str {
+        self.page
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or("")
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub struct EntryDataCSV {
     pub display_name: String,
@@ -0,0 +1,172 @@
+//! Works out what changed between two crawls so the api can push
+//! updates to the front end instead of it polling for them.
+//!
+//! The updates go out on `/live` (a websocket) and `/live/events` (server-sent events), which
+//! are served by the api directly and not through the api gateway, it can't keep them open.
+
+use std::collections::HashMap;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{output::OutputDir, ContestData, EntryData, Money, GLOBAL_LEADERBOARD_SIZE};
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveUpdate {
+    // the global leaderboard changed, this is the whole new leaderboard
+    Leaderboard {
+        entries: Vec<EntryData>,
+    },
+    // one of the contests raised more money or got more entries
+    ContestTotal {
+        contest: String,
+        display_name: String,
+        goal: Money,
+        raised: Money,
+        champ_day: Money,
+        total_entries: usize,
+        timestamp: i64,
+    },
+    // a dog moved up or down within its contest
+    Rank {
+        contest: String,
+        entry_id: String,
+        dog: String,
+        // None if the dog was not in the contest's top dogs before
+        previous_rank: Option<usize>,
+        rank: usize,
+        votes: usize,
+        timestamp: i64,
+    },
+}
+
+impl LiveUpdate {
+    /// The contest page the update is about, None for updates about every contest
+    pub fn contest(&self) -> Option<&str> {
+        match self {
+            LiveUpdate::Leaderboard { .. } => None,
+            LiveUpdate::ContestTotal { contest, .. } => Some(contest),
+            LiveUpdate::Rank { contest, .. } => Some(contest),
+        }
+    }
+
+    /// What kind of update this is, same as the `type` field in the json
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LiveUpdate::Leaderboard { .. } => "leaderboard",
+            LiveUpdate::ContestTotal { .. } => "contest_total",
+            LiveUpdate::Rank { .. } => "rank",
+        }
+    }
+
+    fn contest_total(data: &ContestData) -> LiveUpdate {
+        LiveUpdate::ContestTotal {
+            contest: data.contest.page.clone(),
+            display_name: data.contest.display_name.clone(),
+            goal: data.goal,
+            raised: data.raised,
+            champ_day: data.champ_day,
+            total_entries: data.total_entries,
+            timestamp: data.timestamp,
+        }
+    }
+}
+
+/// Everything we know after a crawl, the dogs are sorted by votes like `top-dogs.json`
+#[derive(Debug, Default, Clone)]
+pub struct LiveState {
+    pub dogs: Vec<EntryData>,
+    pub goals: Vec<ContestData>,
+}
+
+impl LiveState {
+    pub fn leaderboard(&self) -> Vec<EntryData> {
+        self.dogs.iter().take(GLOBAL_LEADERBOARD_SIZE).cloned().collect()
+    }
+
+    /// What a new subscriber gets sent so it doesn't have to wait for the next change
+    pub fn initial_updates(&self) -> Vec<LiveUpdate> {
+        let mut updates = vec![LiveUpdate::Leaderboard { entries: self.leaderboard() }];
+        updates.extend(self.goals.iter().map(LiveUpdate::contest_total));
+
+        updates
+    }
+
+    /// Read the last crawls back out of the files the crawlers write. A missing file just means
+    /// that crawler hasn't run yet, but a file that doesn't parse is broken somehow, so that is
+    /// None instead of a bogus state.
+    pub fn read(output: &OutputDir) -> Option<LiveState> {
+        fn read<T: DeserializeOwned + Default>(output: &OutputDir, file: &str) -> Option<T> {
+            match output.read_to_string(file) {
+                Ok(content) => serde_json::from_str(&content).ok(),
+                Err(_) => Some(T::default()),
+            }
+        }
+
+        Some(LiveState {
+            dogs: read(output, "top-dogs.json")?,
+            goals: read(output, "contest-goals.json")?,
+        })
+    }
+
+    // contest page -> entry url -> rank within the contest, starting at 1
+    fn contest_ranks(&self) -> HashMap<&str, HashMap<&str, usize>> {
+        let mut ranks: HashMap<&str, HashMap<&str, usize>> = HashMap::new();
+
+        for dog in self.dogs.iter() {
+            let contest = ranks.entry(&dog.contest.page).or_default();
+            let rank = contest.len() + 1;
+            contest.insert(&dog.page, rank);
+        }
+
+        ranks
+    }
+}
+
+/// Everything that changed going from the `previous` crawl to the `current` one
+pub fn diff(previous: &LiveState, current: &LiveState) -> Vec<LiveUpdate> {
+    let mut updates = vec![];
+
+    let leaderboard_key = |state: &LiveState| -> Vec<(String, usize)> {
+        state.leaderboard().into_iter().map(|dog| (dog.page, dog.votes)).collect()
+    };
+
+    if leaderboard_key(previous) != leaderboard_key(current) {
+        updates.push(LiveUpdate::Leaderboard { entries: current.leaderboard() });
+    }
+
+    for data in current.goals.iter() {
+        let changed = match previous.goals.iter().find(|p| p.contest.page == data.contest.page) {
+            Some(p) => (p.goal, p.raised, p.champ_day, p.total_entries) != (data.goal, data.raised, data.champ_day, data.total_entries),
+            None => true,
+        };
+
+        if changed {
+            updates.push(LiveUpdate::contest_total(data));
+        }
+    }
+
+    let previous_ranks = previous.contest_ranks();
+    let current_ranks = current.contest_ranks();
+    for dog in current.dogs.iter() {
+        let rank = current_ranks[dog.contest.page.as_str()][dog.page.as_str()];
+        let previous_rank = previous_ranks
+            .get(dog.contest.page.as_str())
+            .and_then(|contest| contest.get(dog.page.as_str()))
+            .copied();
+
+        if previous_rank != Some(rank) {
+            updates.push(LiveUpdate::Rank {
+                contest: dog.contest.page.clone(),
+                entry_id: dog.entry_id().to_string(),
+                dog: dog.dog.clone(),
+                previous_rank,
+                rank,
+                votes: dog.votes,
+                timestamp: dog.timestamp,
+            });
+        }
+    }
+
+    updates
+}
@@ -0,0 +1,226 @@
+//! Amounts of money, kept as whole cents so nothing gets lost to rounding.
+//! They go out as dollars in the json and csv files so `1250` still means $1,250.
+
+use std::{
+    error::Error,
+    fmt,
+    iter::Sum,
+    ops::{Add, AddAssign, Sub},
+};
+
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money {
+    cents: i64,
+}
+
+impl Money {
+    pub const ZERO: Money = Money { cents: 0 };
+
+    pub fn from_cents(cents: i64) -> Money {
+        Money { cents }
+    }
+
+    pub fn from_dollars(dollars: i64) -> Money {
+        Money { cents: dollars * 100 }
+    }
+
+    pub fn cents(self) -> i64 {
+        self.cents
+    }
+
+    pub fn dollars(self) -> f64 {
+        self.cents as f64 / 100.0
+    }
+
+    /// Read an amount like "$1,234.50", "1.234,50 €", "USD 1 234" or "$5,000 raised". Whichever of
+    /// `,` or `.` comes last with one or two digits after it is the decimal point, the rest of the
+    /// `,`, `.`, `'` and spaces between the digits are thousands separators.
+    pub fn parse(text: &str) -> Result<Money, MoneyError> {
+        let error = |reason: &'static str| MoneyError { text: text.trim().to_string(), reason };
+
+        // the amount is the first run of digits and separators, the currency and any words around it get dropped
+        let is_separator = |ch: char| matches!(ch, ',' | '.' | '\'' | ' ' | '\u{a0}' | '\u{202f}');
+        let amount: String = text
+            .chars()
+            .skip_while(|ch| !ch.is_ascii_digit())
+            .take_while(|ch| ch.is_ascii_digit() || is_separator(*ch))
+            .collect();
+        let amount = amount.trim_end_matches(is_separator);
+
+        if amount.is_empty() {
+            return Err(error("there are no digits in it"));
+        }
+
+        // spaces and apostrophes are only ever used to group the thousands
+        let amount: String = amount.chars().filter(|ch| !matches!(ch, '\'' | ' ' | '\u{a0}' | '\u{202f}')).collect();
+
+        let decimal_point = match (amount.rfind('.'), amount.rfind(',')) {
+            (Some(dot), Some(comma)) => Some(dot.max(comma)),
+            (Some(at), None) | (None, Some(at)) => {
+                let separator = amount.as_bytes()[at];
+                let once = amount.bytes().filter(|b| *b == separator).count() == 1;
+                // "1,234" and "1.234" are thousands, "1,5" and "12.50" are not
+                if once && amount.len() - at - 1 != 3 {
+                    Some(at)
+                } else {
+                    None
+                }
+            }
+            (None, None) => None,
+        };
+
+        let (whole, fraction) = match decimal_point {
+            Some(at) => (&amount[..at], &amount[at + 1..]),
+            None => (amount.as_str(), ""),
+        };
+
+        if fraction.len() > 2 {
+            return Err(error("it has more than two digits of cents"));
+        }
+        if fraction.contains(|ch: char| !ch.is_ascii_digit()) {
+            return Err(error("it has more than one decimal point"));
+        }
+
+        // every group after the first one has to be three digits
+        let mut groups = whole.split([',', '.']);
+        let first = groups.next().unwrap_or("");
+        if first.is_empty() || groups.any(|group| group.len() != 3) {
+            return Err(error("the thousands separators are in the wrong place"));
+        }
+
+        let digits: String = whole.chars().filter(char::is_ascii_digit).collect();
+        let dollars: i64 = digits.parse().map_err(|_| error("it is too big"))?;
+        let cents: i64 = format!("{:0<2}", fraction).parse().unwrap_or(0);
+
+        dollars
+            .checked_mul(100)
+            .and_then(|total| total.checked_add(cents))
+            .map(Money::from_cents)
+            .ok_or_else(|| error("it is too big"))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.cents < 0 { "-" } else { "" };
+        let dollars = (self.cents / 100).abs().to_string();
+        let cents = (self.cents % 100).abs();
+
+        // group the dollars in threes
+        let mut grouped = String::new();
+        for (i, ch) in dollars.chars().enumerate() {
+            if i > 0 && (dollars.len() - i).is_multiple_of(3) {
+                grouped.push(',');
+            }
+            grouped.push(ch);
+        }
+
+        if cents == 0 {
+            write!(f, "{}${}", sign, grouped)
+        } else {
+            write!(f, "{}${}.{:02}", sign, grouped, cents)
+        }
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money::from_cents(self.cents + other.cents)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.cents += other.cents;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money::from_cents(self.cents - other.cents)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+// whole dollars stay integers so the files look the same as they always have
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.cents % 100 == 0 {
+            serializer.serialize_i64(self.cents / 100)
+        } else {
+            serializer.serialize_f64(self.dollars())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        struct DollarsVisitor;
+
+        impl<'de> de::Visitor<'de> for DollarsVisitor {
+            type Value = Money;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an amount of dollars")
+            }
+
+            fn visit_i64<E: de::Error>(self, dollars: i64) -> Result<Money, E> {
+                Ok(Money::from_dollars(dollars))
+            }
+
+            fn visit_u64<E: de::Error>(self, dollars: u64) -> Result<Money, E> {
+                Ok(Money::from_dollars(dollars as i64))
+            }
+
+            fn visit_f64<E: de::Error>(self, dollars: f64) -> Result<Money, E> {
+                Ok(Money::from_cents((dollars * 100.0).round() as i64))
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<Money, E> {
+                Money::parse(text).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(DollarsVisitor)
+    }
+}
+
+// and cents in the history database
+impl ToSql for Money {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.cents))
+    }
+}
+
+impl FromSql for Money {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Money> {
+        i64::column_result(value).map(Money::from_cents)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoneyError {
+    // what we were trying to read
+    pub text: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unable to read an amount of money from {:?}: {}", self.text, self.reason)
+    }
+}
+
+impl Error for MoneyError {}
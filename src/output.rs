@@ -0,0 +1,243 @@
+//! The files the crawlers write for the api and the uploader to pick up. Every file is written
+//! to a temp file next to it and renamed into place, so whoever reads it only ever sees the
+//! whole file from one tick or the whole file from the next one, never half of one.
+//!
+//! When everything runs in one process the api reads the crawls from the `SnapshotStore`
+//! instead, so the files can be turned off with `--write-files false`.
+
+use std::{
+    error::Error,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use chrono::Utc;
+use log::debug;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::config;
+
+// where the files go if nobody tells us otherwise, which is where they always went
+pub const DEFAULT_OUTPUT_DIR: &str = ".";
+
+// every temp file gets its own name so two writers of the same file can't trip over each other
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// The directory the crawlers write their files to and everything else reads them from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputDir {
+    root: PathBuf,
+    // when it is off nothing gets written and there is never anything to read
+    enabled: bool,
+}
+
+impl OutputDir {
+    pub fn new<P: Into<PathBuf>>(root: P) -> OutputDir {
+        OutputDir { root: root.into(), enabled: true }
+    }
+
+    /// An output directory that takes every write and throws it away
+    pub fn disabled<P: Into<PathBuf>>(root: P) -> OutputDir {
+        OutputDir { root: root.into(), enabled: false }
+    }
+
+    /// The directory from `--output-dir` or `OUTPUT_DIR`, the working directory if neither is set.
+    /// It is turned off with `--write-files false` (or `WRITE_FILES=false`).
+    pub fn from_settings() -> OutputDir {
+        let root = config::setting("--output-dir", "OUTPUT_DIR").unwrap_or_else(|| DEFAULT_OUTPUT_DIR.into());
+
+        if config::parsed_setting("--write-files", "WRITE_FILES").unwrap_or(true) {
+            OutputDir::new(root)
+        } else {
+            OutputDir::disabled(root)
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    pub fn read_to_string(&self, name: &str) -> io::Result<String> {
+        if !self.enabled {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "writing files is turned off"));
+        }
+
+        fs::read_to_string(self.path(name))
+    }
+
+    /// Read a json file we wrote earlier, a missing file is `Ok(None)`
+    pub fn read_json<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, Box<dyn Error>> {
+        match self.read_to_string(name) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replace `name` with `contents` in one go
+    pub fn write(&self, name: &str, contents: &[u8]) -> io::Result<OutputFile> {
+        let file = OutputFile {
+            name: name.to_string(),
+            bytes: contents.len() as u64,
+            rows: None,
+        };
+        if !self.enabled {
+            return Ok(file);
+        }
+
+        // names can have directories in them, like the partitions of the exports
+        let path = self.path(name);
+        fs::create_dir_all(path.parent().unwrap_or(&self.root))?;
+
+        let file_name = path.file_name().map(|file_name| file_name.to_string_lossy().to_string()).unwrap_or_default();
+        let temp = path.with_file_name(format!(
+            ".{}.{}-{}.tmp",
+            file_name,
+            std::process::id(),
+            TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let written = write_synced(&temp, contents).and_then(|_| fs::rename(&temp, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        written?;
+
+        debug!("wrote file; file={}; bytes={}", path.display(), contents.len());
+        Ok(file)
+    }
+
+    pub fn write_json<T: Serialize + ?Sized>(&self, name: &str, value: &T) -> Result<OutputFile, Box<dyn Error>> {
+        Ok(self.write(name, &serde_json::to_vec(value)?)?)
+    }
+
+    /// Write a json array, keeping track of how many rows went in to it for the manifest
+    pub fn write_json_rows<T: Serialize>(&self, name: &str, rows: &[T]) -> Result<OutputFile, Box<dyn Error>> {
+        let file = self.write_json(name, rows)?;
+        Ok(file.with_rows(rows.len()))
+    }
+
+    pub fn write_csv<T, I>(&self, name: &str, records: I) -> Result<OutputFile, Box<dyn Error>>
+    where
+        T: Serialize,
+        I: IntoIterator<Item = T>,
+    {
+        let (contents, rows) = csv_bytes(records)?;
+
+        let file = self.write(name, &contents)?;
+        Ok(file.with_rows(rows))
+    }
+
+    /// Move `name` out of the way under a name of its own so it can be worked on without the
+    /// crawler writing over it, or deleting the next one by mistake. `Ok(None)` if it isn't there.
+    pub fn claim(&self, name: &str, claimed: &str) -> io::Result<Option<PathBuf>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        let claimed = self.path(claimed);
+
+        match fs::rename(self.path(name), &claimed) {
+            Ok(()) => Ok(Some(claimed)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Turn `records` into a csv file, and how many rows went in to it
+pub fn csv_bytes<T, I>(records: I) -> Result<(Vec<u8>, usize), Box<dyn Error>>
+where
+    T: Serialize,
+    I: IntoIterator<Item = T>,
+{
+    let mut csv_wtr = csv::Writer::from_writer(Vec::new());
+    let mut rows = 0;
+    for record in records {
+        csv_wtr.serialize(record)?;
+        rows += 1;
+    }
+
+    Ok((csv_wtr.into_inner().map_err(|e| e.into_error())?, rows))
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(contents)?;
+    // make sure it is actually on disk before it replaces the old one
+    file.sync_all()
+}
+
+/// One of the files written in a tick
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputFile {
+    pub name: String,
+    pub bytes: u64,
+    // how many dogs or contests are in it, for the json arrays and csv files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<usize>,
+}
+
+impl OutputFile {
+    fn with_rows(mut self, rows: usize) -> OutputFile {
+        self.rows = Some(rows);
+        self
+    }
+}
+
+/// Ties together the files one crawler wrote in one tick. It is written after all
+/// of them, so when it says tick N every file in it is from tick N.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub crawler: String,
+    // the timestamp of the tick, the same one the crawled data has
+    pub tick: i64,
+    pub written_at: i64,
+    pub files: Vec<OutputFile>,
+}
+
+impl Manifest {
+    pub fn new(crawler: &str, tick: i64) -> Manifest {
+        Manifest {
+            crawler: crawler.to_string(),
+            tick,
+            written_at: 0,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn file_name(crawler: &str) -> String {
+        format!("{}-manifest.json", crawler)
+    }
+
+    pub fn add(&mut self, file: OutputFile) {
+        self.files.push(file);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OutputFile> {
+        self.files.iter().find(|file| file.name == name)
+    }
+
+    /// Write the manifest once everything in it has been written
+    pub fn write(mut self, output: &OutputDir) -> Result<Manifest, Box<dyn Error>> {
+        self.written_at = Utc::now().timestamp();
+        output.write_json(&Manifest::file_name(&self.crawler), &self)?;
+
+        Ok(self)
+    }
+
+    /// The last manifest `crawler` wrote, if it has written one
+    pub fn read(output: &OutputDir, crawler: &str) -> Result<Option<Manifest>, Box<dyn Error>> {
+        output.read_json(&Manifest::file_name(crawler))
+    }
+}
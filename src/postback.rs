@@ -0,0 +1,79 @@
+//! gogophoto is an ASP.NET site, so paging through the search results is done with
+//! postbacks: the pager links run `__doPostBack(target, argument)`, which posts the form
+//! back to the same page along with the hidden view state.
+
+use nipper::Document;
+
+use crate::selectors::SelectorProfile;
+
+/// The event a pager link fires
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Postback {
+    pub event_target: String,
+    pub event_argument: String,
+}
+
+/// Pull the target and argument out of a `javascript:__doPostBack('target','argument')` link
+pub fn parse_do_postback(href: &str) -> Option<Postback> {
+    let start = href.find("__doPostBack(")? + "__doPostBack(".len();
+    let end = start + href[start..].find(')')?;
+
+    let mut args = href[start..end].split(',').map(|arg| {
+        arg.trim()
+            .trim_matches(|ch| ch == '\'' || ch == '"')
+            .to_string()
+    });
+
+    let event_target = args.next().filter(|target| !target.is_empty())?;
+    let event_argument = args.next().unwrap_or_default();
+
+    Some(Postback { event_target, event_argument })
+}
+
+/// Find the pager link that goes to `page` (counting from 1), falling back on a "next" link
+/// for pagers that do not number their pages
+pub fn next_page(doc: &Document, selectors: &SelectorProfile, page: usize) -> Option<Postback> {
+    let links: Vec<(String, String, String)> = doc
+        .select(&selectors.search_pager)
+        .iter()
+        .filter_map(|link| {
+            let href = link.attr("href")?.to_string();
+            let id = link.attr("id").map(|id| id.to_string()).unwrap_or_default();
+            Some((href, id, link.text().trim().to_string()))
+        })
+        .collect();
+
+    let numbered = links.iter().find(|(_, _, text)| text.parse::<usize>().ok() == Some(page));
+    let next = || {
+        links.iter().find(|(href, id, text)| {
+            let text = text.to_lowercase();
+            text.starts_with("next")
+                || text == ">"
+                || text == "›"
+                || text == "»"
+                || id.to_lowercase().contains("next")
+                || href.to_lowercase().contains("next")
+        })
+    };
+
+    numbered.or_else(next).and_then(|(href, _, _)| parse_do_postback(href))
+}
+
+/// The form to post to fire `postback`, the hidden ASP.NET state of the page plus the event
+pub fn form_fields(doc: &Document, selectors: &SelectorProfile, postback: &Postback) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = doc
+        .select(&selectors.search_state)
+        .iter()
+        .filter_map(|input| {
+            let name = input.attr("name")?.to_string();
+            let value = input.attr("value").map(|value| value.to_string()).unwrap_or_default();
+            Some((name, value))
+        })
+        .filter(|(name, _)| name != "__EVENTTARGET" && name != "__EVENTARGUMENT")
+        .collect();
+
+    fields.push(("__EVENTTARGET".to_string(), postback.event_target.clone()));
+    fields.push(("__EVENTARGUMENT".to_string(), postback.event_argument.clone()));
+
+    fields
+}
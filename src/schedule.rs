@@ -0,0 +1,122 @@
+//! When the contests run. A contest is upcoming before its `starts_at`, live until its
+//! `voting_closes_at` (or `ends_at` without one) and closed after that, contests without
+//! any dates are always live.
+//!
+//! The crawlers only crawl the live contests and give each contest one last closing crawl
+//! once it closes, after that its last data just gets carried forward. When nothing is
+//! live they only wake up every `--idle-interval-minutes` (or `IDLE_INTERVAL_MINUTES`,
+//! 15 by default), or when the next contest starts if that is sooner.
+
+use std::{collections::HashSet, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config, Contest};
+
+// how often the live contests get crawled
+pub const CRAWL_INTERVAL: Duration = Duration::from_secs(60);
+
+pub const DEFAULT_IDLE_INTERVAL_MINUTES: u64 = 15;
+
+// how long a closing crawl that keeps failing gets retried before we give up on it
+const CLOSING_GRACE_SECONDS: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ContestStatus {
+    Upcoming,
+    Live,
+    Closed,
+}
+
+impl ContestStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContestStatus::Upcoming => "upcoming",
+            ContestStatus::Live => "live",
+            ContestStatus::Closed => "closed",
+        }
+    }
+}
+
+/// What a crawler does in one tick
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tick {
+    // the contests to crawl, the live ones and the ones getting their closing crawl
+    pub crawl: Vec<Contest>,
+    // the pages of the contests getting their closing crawl
+    pub closing: Vec<String>,
+    // the pages of the closed contests that are done being crawled
+    pub closed: Vec<String>,
+    // how long until the next tick
+    pub wait: Duration,
+}
+
+impl Tick {
+    /// Nothing to crawl, so nothing gets written either
+    pub fn idle(&self) -> bool {
+        self.crawl.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    // how long to wait between ticks when no contest is live
+    pub idle_interval: Duration,
+}
+
+impl Default for Schedule {
+    fn default() -> Schedule {
+        Schedule {
+            idle_interval: Duration::from_secs(DEFAULT_IDLE_INTERVAL_MINUTES * 60),
+        }
+    }
+}
+
+impl Schedule {
+    /// The idle interval from `--idle-interval-minutes` or `IDLE_INTERVAL_MINUTES`
+    pub fn from_settings() -> Schedule {
+        let minutes = config::parsed_setting("--idle-interval-minutes", "IDLE_INTERVAL_MINUTES")
+            .filter(|minutes: &u64| *minutes > 0)
+            .unwrap_or(DEFAULT_IDLE_INTERVAL_MINUTES);
+
+        Schedule {
+            idle_interval: Duration::from_secs(minutes * 60),
+        }
+    }
+
+    /// Work out the tick at `now`, `closing_crawls` are the pages of the
+    /// contests that already had their closing crawl
+    pub fn plan(&self, contests: &[Contest], closing_crawls: &HashSet<String>, now: i64) -> Tick {
+        let mut tick = Tick {
+            crawl: Vec::new(),
+            closing: Vec::new(),
+            closed: Vec::new(),
+            wait: self.idle_interval,
+        };
+
+        for contest in contests {
+            match contest.status(now) {
+                ContestStatus::Upcoming => {}
+                ContestStatus::Live => tick.crawl.push(contest.clone()),
+                ContestStatus::Closed => {
+                    let closes_at = contest.closes_at().unwrap_or(now);
+                    if closing_crawls.contains(&contest.page) || now - closes_at > CLOSING_GRACE_SECONDS {
+                        tick.closed.push(contest.page.clone());
+                    } else {
+                        tick.closing.push(contest.page.clone());
+                        tick.crawl.push(contest.clone());
+                    }
+                }
+            }
+        }
+
+        if !tick.idle() {
+            tick.wait = CRAWL_INTERVAL;
+        } else if let Some(next_start) = contests.iter().filter_map(|contest| contest.starts_at).filter(|starts_at| *starts_at > now).min() {
+            tick.wait = tick.wait.min(Duration::from_secs((next_start - now) as u64));
+        }
+
+        tick
+    }
+}
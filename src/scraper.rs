@@ -0,0 +1,389 @@
+//! Everything that knows what gogophotocontest.com pages look like. The crawler tasks
+//! use it to do the scraping, and so can anything else that wants the same data.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use chrono::Utc;
+use log::{debug, info, warn};
+use nipper::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::{fetch::Fetcher, postback, selectors::SelectorProfile, Contest, EntryData, Money};
+
+#[derive(Debug)]
+pub enum ScrapeError {
+    // the page could not be fetched
+    Request { url: String, source: reqwest::Error },
+    // nothing on the page matched the selector, usually means the site changed
+    Missing { url: String, field: &'static str, selector: String },
+    // the selector matched but what it matched is not a number
+    Parse { url: String, field: &'static str, text: String },
+}
+
+impl fmt::Display for ScrapeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScrapeError::Request { url, source } => write!(f, "{}: request failed: {}", url, source),
+            ScrapeError::Missing { url, field, selector } => write!(f, "{}: no {} on the page (selector {:?})", url, field, selector),
+            ScrapeError::Parse { url, field, text } => write!(f, "{}: unable to read the {} from {:?}", url, field, text),
+        }
+    }
+}
+
+impl Error for ScrapeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScrapeError::Request { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the required selectors kept finding what they are after
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrapeHealth {
+    pub healthy: bool,
+    // how many times each required field was missing from a page
+    pub missing: BTreeMap<String, usize>,
+}
+
+impl Default for ScrapeHealth {
+    fn default() -> ScrapeHealth {
+        ScrapeHealth {
+            healthy: true,
+            missing: BTreeMap::new(),
+        }
+    }
+}
+
+/// The fundraising totals on the front page of a contest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ContestSummary {
+    pub raised: Money,
+    pub goal: Money,
+    pub total_entries: usize,
+}
+
+/// How far down the leaderboard of a contest to go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrawlDepth {
+    // the first `num_dogs` entries on the first page of the search results
+    Top,
+    // every entry in the contest, following the pagination for up to `max_pages` pages
+    Full { max_pages: usize },
+}
+
+/// How much of a contest a search saw, so the dogs we know about can be
+/// reconciled with the total entries the contest says it has
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Coverage {
+    pub page: String,
+    // how many entries the contest says it has, None when a top dogs crawl couldn't read it
+    pub total_entries: Option<usize>,
+    // how many entries we found on the search pages
+    pub found: usize,
+    pub pages: usize,
+    // there were more pages but we hit the page cap
+    pub capped: bool,
+    pub timestamp: i64,
+}
+
+/// The entry pages on the leaderboard of a contest, in leaderboard order
+#[derive(Debug, Clone)]
+pub struct SearchResults {
+    pub entry_urls: Vec<String>,
+    pub coverage: Coverage,
+}
+
+#[derive(Clone)]
+pub struct GogoPhotoClient {
+    fetcher: Fetcher,
+    domain: String,
+    selectors: Arc<SelectorProfile>,
+    missing: Arc<Mutex<BTreeMap<String, usize>>>,
+}
+
+impl GogoPhotoClient {
+    /// A client using the default selectors
+    pub fn new(fetcher: Fetcher, domain: &str) -> GogoPhotoClient {
+        GogoPhotoClient {
+            fetcher,
+            domain: domain.trim_end_matches('/').to_string(),
+            selectors: Arc::new(SelectorProfile::default()),
+            missing: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    pub fn with_selectors(mut self, selectors: SelectorProfile) -> GogoPhotoClient {
+        self.selectors = Arc::new(selectors);
+        self
+    }
+
+    pub fn selectors(&self) -> &SelectorProfile {
+        &self.selectors
+    }
+
+    /// Which required selectors have come up empty since the last time this was called
+    pub fn take_health(&self) -> ScrapeHealth {
+        let missing = std::mem::take(&mut *self.missing.lock().unwrap());
+
+        ScrapeHealth {
+            healthy: missing.is_empty(),
+            missing,
+        }
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn fetcher(&self) -> &Fetcher {
+        &self.fetcher
+    }
+
+    pub fn contest_url(&self, contest: &Contest) -> String {
+        format!("{}/{}", self.domain, contest.page)
+    }
+
+    pub fn search_url(&self, contest: &Contest) -> String {
+        format!("{}/{}/search", self.domain, contest.page)
+    }
+
+    /// The fundraising meter of a contest and how many entries it has
+    pub async fn contest_summary(&self, contest: &Contest) -> Result<ContestSummary, ScrapeError> {
+        let url = self.contest_url(contest);
+        let html = self.get(&url).await?;
+
+        let (raised, goal) = {
+            let doc = Document::from(&html);
+            let raised = self.require(money(&doc, &url, "contest_raised", &self.selectors.contest_raised))?;
+            // not every contest has a goal
+            let goal = optional(money(&doc, &url, "contest_goal", &self.selectors.contest_goal))?.unwrap_or(Money::ZERO);
+            (raised, goal)
+        };
+
+        let search_url = self.search_url(contest);
+        let html = self.get(&search_url).await?;
+        let total_entries = self.require(number(&Document::from(&html), &search_url, "search_total", &self.selectors.search_total))?;
+
+        Ok(ContestSummary { raised, goal, total_entries })
+    }
+
+    /// The entry pages of a contest, only the top `num_dogs` unless `depth` says to go through every page
+    pub async fn search_entries(&self, contest: &Contest, depth: CrawlDepth) -> Result<SearchResults, ScrapeError> {
+        let url = self.search_url(contest);
+        info!("getting url; url={:?}", url);
+
+        let mut html = self.get(&url).await?;
+
+        let mut entry_urls: Vec<String> = vec![];
+        let mut coverage = Coverage {
+            page: contest.page.clone(),
+            total_entries: None,
+            found: 0,
+            pages: 0,
+            capped: false,
+            timestamp: Utc::now().timestamp(),
+        };
+
+        loop {
+            let next_form = {
+                let doc = Document::from(&html);
+                coverage.pages += 1;
+
+                let links = self.entry_links(&doc);
+
+                if coverage.pages == 1 {
+                    let total = number(&doc, &url, "search_total", &self.selectors.search_total);
+                    coverage.total_entries = match (depth, total) {
+                        // the top dogs are all we are after, so not knowing how many there are is fine
+                        (CrawlDepth::Top, Err(e)) => {
+                            warn!("Unable to read the total entries, the coverage is unknown; contest={}; error={}", contest.page, e);
+                            None
+                        }
+                        (_, total) => Some(self.require(total)?),
+                    };
+
+                    // a contest with entries should always have some on its first page
+                    if links.is_empty() && coverage.total_entries.is_some_and(|total| total > 0) {
+                        return Err(self.missing(&url, "search_entries", &self.selectors.search_entries));
+                    }
+                }
+
+                // a page we have already seen means the pager sent us in a circle
+                let new_links: Vec<String> = links.into_iter().filter(|link| !entry_urls.contains(link)).collect();
+                if new_links.is_empty() {
+                    break;
+                }
+                entry_urls.extend(new_links);
+
+                let max_pages = match depth {
+                    CrawlDepth::Top => {
+                        entry_urls.truncate(contest.num_dogs);
+                        break;
+                    }
+                    CrawlDepth::Full { max_pages } => max_pages,
+                };
+
+                let next = match postback::next_page(&doc, &self.selectors, coverage.pages + 1) {
+                    Some(next) => next,
+                    None => break,
+                };
+
+                if coverage.pages >= max_pages {
+                    warn!("Hit the page cap before the end of the contest; contest={}; pages={}", contest.page, coverage.pages);
+                    coverage.capped = true;
+                    break;
+                }
+
+                postback::form_fields(&doc, &self.selectors, &next)
+            };
+
+            debug!("getting next page of results; contest={}; page={}", contest.page, coverage.pages + 1);
+            html = self.fetcher.post_form(&url, &next_form).await.map_err(|source| ScrapeError::Request { url: url.clone(), source })?;
+        }
+
+        coverage.found = entry_urls.len();
+        if let CrawlDepth::Full { .. } = depth {
+            info!(
+                "listed contest entries; contest={}; found={}; total_entries={:?}; pages={}; capped={}",
+                contest.page, coverage.found, coverage.total_entries, coverage.pages, coverage.capped,
+            );
+            if coverage.total_entries != Some(coverage.found) {
+                warn!("Found a different number of entries than the contest has; contest={}; found={}; total_entries={:?}", contest.page, coverage.found, coverage.total_entries);
+            }
+        }
+
+        Ok(SearchResults { entry_urls, coverage })
+    }
+
+    /// Everything on the page of a single entry
+    pub async fn entry_detail(&self, contest: &Contest, entry_url: &str) -> Result<EntryData, ScrapeError> {
+        info!("getting url; url={:?}", entry_url);
+
+        let html = self.get(entry_url).await?;
+        let doc = Document::from(&html);
+
+        let dog: String = doc.select(&self.selectors.entry_name)
+            .text()
+            .split('\n')
+            .take(2)
+            .collect::<String>()
+            .trim()
+            .into();
+
+        if dog.is_empty() {
+            return Err(self.missing(entry_url, "entry_name", &self.selectors.entry_name));
+        }
+
+        debug!("selected dog; dog={}", dog);
+
+        let votes = self.require(number(&doc, entry_url, "entry_votes", &self.selectors.entry_votes))?;
+        debug!("selected votes; votes={}", votes);
+
+        // entries in contests that do not raise money do not have this
+        let raised = optional(money(&doc, entry_url, "entry_raised", &self.selectors.entry_raised))?.unwrap_or(Money::ZERO);
+
+        let category = doc.select(&self.selectors.entry_category)
+            .text()
+            .to_string()
+            .replace("Entry Category:", "")
+            .trim()
+            .to_string();
+
+        let picture: String = doc.select(&self.selectors.entry_picture)
+            .attr("src")
+            .map_or(String::from(""), |v| v.to_string());
+
+        debug!("selected picture; picture={}", picture);
+
+        Ok(EntryData {
+            dog,
+            votes,
+            raised,
+            contest: contest.clone(),
+            category,
+            page: String::from(entry_url),
+            picture: format!("{}{}", self.domain, picture),
+            timestamp: Utc::now().timestamp(),
+            stale: false,
+            stale_age: 0,
+        })
+    }
+
+    // a required field that is not on the page, kept track of so the tick can be flagged as unhealthy
+    fn missing(&self, url: &str, field: &'static str, selector: &str) -> ScrapeError {
+        warn!("Required selector matched nothing; field={}; selector={:?}; url={}", field, selector, url);
+        *self.missing.lock().unwrap().entry(field.to_string()).or_insert(0) += 1;
+
+        ScrapeError::Missing { url: url.to_string(), field, selector: selector.to_string() }
+    }
+
+    fn require<T>(&self, result: Result<T, ScrapeError>) -> Result<T, ScrapeError> {
+        result.map_err(|e| match e {
+            ScrapeError::Missing { url, field, selector } => self.missing(&url, field, &selector),
+            e => e,
+        })
+    }
+
+    async fn get(&self, url: &str) -> Result<String, ScrapeError> {
+        self.fetcher.get_text(url).await.map_err(|source| ScrapeError::Request { url: url.to_string(), source })
+    }
+
+    // the links to the entry pages on a page of search results, in leaderboard order
+    fn entry_links(&self, doc: &Document) -> Vec<String> {
+        doc.select(&self.selectors.search_entries)
+            .iter()
+            .filter_map(|entry_link| {
+                let entry_link_str = entry_link.attr("href")?;
+                debug!("selected entry; entry_url={}", entry_link_str);
+                Some(format!("{}{}", self.domain, entry_link_str))
+            })
+            .collect()
+    }
+}
+
+// the text of whatever `selector` matches, as long as it matches something
+fn select_text(doc: &Document, url: &str, field: &'static str, selector: &str) -> Result<String, ScrapeError> {
+    let selection = doc.select(selector);
+    if !selection.exists() {
+        return Err(ScrapeError::Missing { url: url.to_string(), field, selector: selector.to_string() });
+    }
+
+    Ok(selection.text().trim().to_string())
+}
+
+// read a count like "1,250 Votes" out of whatever `selector` matches
+fn number(doc: &Document, url: &str, field: &'static str, selector: &str) -> Result<usize, ScrapeError> {
+    let text = select_text(doc, url, field, selector)?;
+
+    text.chars()
+        .filter(|ch| ch.is_ascii_digit())
+        .collect::<String>()
+        .parse()
+        .map_err(|_| ScrapeError::Parse { url: url.to_string(), field, text })
+}
+
+// read an amount like "$3,210.50" out of whatever `selector` matches
+fn money(doc: &Document, url: &str, field: &'static str, selector: &str) -> Result<Money, ScrapeError> {
+    let text = select_text(doc, url, field, selector)?;
+
+    Money::parse(&text).map_err(|e| {
+        debug!("unable to parse money; field={}; error={}", field, e);
+        ScrapeError::Parse { url: url.to_string(), field, text }
+    })
+}
+
+// a field the page does not have to have, it still has to make sense when it is there
+fn optional<T>(result: Result<T, ScrapeError>) -> Result<Option<T>, ScrapeError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ScrapeError::Missing { .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,135 @@
+//! The contests come in seasons, e.g. "Fall 2022" and "Spring 2023", listed oldest first with
+//! `seasons` at the top of the contests file. Only the contests of the newest season get crawled,
+//! the ones of past seasons stay in the file so their history can still be looked up and compared.
+//!
+//! A shelter's contests are matched up across the seasons by their `shelter` key, so each shelter
+//! can be compared with how it did the season before.
+
+use serde::Serialize;
+
+use crate::{
+    history::{self, History},
+    Contest, Contests, Money,
+};
+
+/// How a contest did, from the last crawl of it in the history, which for
+/// a contest that is still going is how it is doing so far
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct SeasonResult {
+    pub season: String,
+    pub shelter: String,
+    // the page of the contest
+    pub contest: String,
+    pub display_name: String,
+    pub goal: Money,
+    // champ day money included
+    pub raised: Money,
+    pub total_entries: usize,
+    // when the last crawl was
+    pub timestamp: i64,
+}
+
+impl SeasonResult {
+    /// None when the contest has never been crawled
+    pub fn load(history: &History, contest: &Contest) -> Result<Option<SeasonResult>, history::Error> {
+        let record = match history.latest_contest(&contest.page)? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+
+        Ok(Some(SeasonResult {
+            season: contest.season.clone(),
+            shelter: contest.shelter_key().to_string(),
+            contest: contest.page.clone(),
+            display_name: contest.display_name.clone(),
+            goal: record.goal,
+            raised: record.raised + record.champ_day,
+            total_entries: record.total_entries,
+            timestamp: record.timestamp,
+        }))
+    }
+}
+
+/// How a shelter did one season next to another
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Comparison {
+    pub shelter: String,
+    // None when the shelter had no contest that season, or it was never crawled
+    pub current: Option<SeasonResult>,
+    pub previous: Option<SeasonResult>,
+    // only when there is both
+    pub raised_change: Option<Money>,
+    // None when the previous contest raised nothing
+    pub raised_change_percent: Option<f64>,
+    pub entries_change: Option<i64>,
+}
+
+impl Comparison {
+    pub fn new(shelter: &str, current: Option<SeasonResult>, previous: Option<SeasonResult>) -> Comparison {
+        let both = current.as_ref().zip(previous.as_ref());
+
+        Comparison {
+            shelter: shelter.to_string(),
+            raised_change: both.map(|(current, previous)| current.raised - previous.raised),
+            raised_change_percent: both
+                .filter(|(_, previous)| previous.raised > Money::ZERO)
+                .map(|(current, previous)| percent(current.raised.cents() - previous.raised.cents(), previous.raised.cents())),
+            entries_change: both.map(|(current, previous)| current.total_entries as i64 - previous.total_entries as i64),
+            current,
+            previous,
+        }
+    }
+}
+
+// to one decimal
+fn percent(change: i64, of: i64) -> f64 {
+    (change as f64 * 1000.0 / of as f64).round() / 10.0
+}
+
+// the shelter's contest in `season`, if it had one
+fn shelter_result(contests: &Contests, history: &History, season: &str, shelter: &str) -> Result<Option<SeasonResult>, history::Error> {
+    match contests.get_all().iter().find(|c| c.season == season && c.shelter_key() == shelter) {
+        Some(contest) => SeasonResult::load(history, contest),
+        None => Ok(None),
+    }
+}
+
+/// How every shelter with a contest in either season did in `season` next to `against`
+pub fn compare(contests: &Contests, history: &History, season: &str, against: &str) -> Result<Vec<Comparison>, history::Error> {
+    let mut shelters: Vec<String> = Vec::new();
+    for contest in contests.get_all().iter().filter(|c| c.season == season || c.season == against) {
+        if !shelters.iter().any(|shelter| shelter == contest.shelter_key()) {
+            shelters.push(contest.shelter_key().to_string());
+        }
+    }
+
+    shelters
+        .iter()
+        .map(|shelter| {
+            let current = shelter_result(contests, history, season, shelter)?;
+            let previous = shelter_result(contests, history, against, shelter)?;
+            Ok(Comparison::new(shelter, current, previous))
+        })
+        .collect()
+}
+
+/// Every season a shelter had a contest in, oldest first, each next to the season right before
+/// it, which has nothing to compare with when the shelter sat that one out
+pub fn shelter_seasons(contests: &Contests, history: &History, shelter: &str) -> Result<Vec<Comparison>, history::Error> {
+    let mut comparisons = Vec::new();
+
+    for season in contests.seasons() {
+        let current = match shelter_result(contests, history, season, shelter)? {
+            Some(current) => current,
+            None => continue,
+        };
+        let previous = match contests.previous_season(season) {
+            Some(previous) => shelter_result(contests, history, previous, shelter)?,
+            None => None,
+        };
+
+        comparisons.push(Comparison::new(shelter, Some(current), previous));
+    }
+
+    Ok(comparisons)
+}
@@ -0,0 +1,208 @@
+//! The css selectors the scraper uses to find things on gogophoto pages. The defaults are
+//! what the site looks like today, a profile file can override any of them when it changes.
+
+use std::path::Path;
+
+use nipper::{Document, Matcher};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, ConfigError};
+
+/// The kinds of gogophoto pages we scrape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageKind {
+    // the front page of a contest with the fundraising meter
+    Contest,
+    // the leaderboard of a contest
+    Search,
+    // the page of a single entry
+    Entry,
+}
+
+impl PageKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            PageKind::Contest => "contest",
+            PageKind::Search => "search",
+            PageKind::Entry => "entry",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<PageKind> {
+        match name {
+            "contest" => Some(PageKind::Contest),
+            "search" => Some(PageKind::Search),
+            "entry" => Some(PageKind::Entry),
+            _ => None,
+        }
+    }
+
+    /// Work out what kind of page a gogophoto url (or a page saved from one) is
+    pub fn guess(url: &str) -> PageKind {
+        let last = url.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+        if url.contains("/entries/") || last.starts_with("entry") {
+            PageKind::Entry
+        } else if last.starts_with("search") {
+            PageKind::Search
+        } else {
+            PageKind::Contest
+        }
+    }
+}
+
+/// A field we scrape, required fields missing from a page make the scrape unhealthy
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    pub name: &'static str,
+    pub page: PageKind,
+    pub required: bool,
+}
+
+pub const FIELDS: [Field; 11] = [
+    Field { name: "entry_name", page: PageKind::Entry, required: true },
+    Field { name: "entry_votes", page: PageKind::Entry, required: true },
+    Field { name: "entry_raised", page: PageKind::Entry, required: false },
+    Field { name: "entry_category", page: PageKind::Entry, required: false },
+    Field { name: "entry_picture", page: PageKind::Entry, required: false },
+    Field { name: "contest_raised", page: PageKind::Contest, required: true },
+    Field { name: "contest_goal", page: PageKind::Contest, required: false },
+    Field { name: "search_entries", page: PageKind::Search, required: true },
+    Field { name: "search_total", page: PageKind::Search, required: true },
+    // a single page of results has no pager
+    Field { name: "search_pager", page: PageKind::Search, required: false },
+    Field { name: "search_state", page: PageKind::Search, required: false },
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SelectorProfile {
+    pub entry_name: String,
+    pub entry_votes: String,
+    pub entry_raised: String,
+    pub entry_category: String,
+    pub entry_picture: String,
+    pub contest_raised: String,
+    pub contest_goal: String,
+    pub search_entries: String,
+    pub search_total: String,
+    // the `__doPostBack` links that page through the results
+    pub search_pager: String,
+    // the hidden ASP.NET state that gets posted back with them
+    pub search_state: String,
+}
+
+impl Default for SelectorProfile {
+    fn default() -> SelectorProfile {
+        SelectorProfile {
+            entry_name: "#form1 > div.main > div.mainBody > div:nth-child(1) > h1".into(),
+            entry_votes: "h3.viewEntryVotes".into(),
+            entry_raised: "#ContentPlaceHolder_divRaised > span".into(),
+            entry_category: "#ContentPlaceHolder_divEntryCategory".into(),
+            entry_picture: "#ContentPlaceHolder_imgEntry".into(),
+            // the goal is a `div > span` in the meter as well so it has to be left out
+            contest_raised: "#ContentPlaceHolder_divFundraisingMeter > div:not(.goal) > span".into(),
+            contest_goal: "#ContentPlaceHolder_divFundraisingMeter > div.goal > span".into(),
+            search_entries: "#ContentPlaceHolder_upPanel .searchEntryCont a.searchEntry".into(),
+            search_total: "#ContentPlaceHolder_divSearchTitle > span.numEntries".into(),
+            search_pager: "#ContentPlaceHolder_upPanel a[href*=__doPostBack]".into(),
+            search_state: "input[type=hidden]".into(),
+        }
+    }
+}
+
+impl SelectorProfile {
+    /// Load the profile from `--selectors` or `SELECTORS_FILE`, sticking with
+    /// the defaults when neither is set. The file only needs the selectors it changes.
+    pub fn load() -> Result<SelectorProfile, ConfigError> {
+        match config::setting("--selectors", "SELECTORS_FILE") {
+            Some(path) => SelectorProfile::from_path(path),
+            None => Ok(SelectorProfile::default()),
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<SelectorProfile, ConfigError> {
+        let path = path.as_ref();
+        let profile: SelectorProfile = config::load_file(path)?;
+
+        // nipper panics on selectors it can not parse, so catch them here instead
+        for field in FIELDS.iter() {
+            let selector = profile.get(field.name);
+            if Matcher::new(selector).is_err() {
+                return Err(ConfigError::Parse {
+                    path: path.display().to_string(),
+                    message: format!("{} is not a valid css selector: {:?}", field.name, selector),
+                });
+            }
+        }
+
+        Ok(profile)
+    }
+
+    /// The selector for one of the `FIELDS`
+    pub fn get(&self, field: &str) -> &str {
+        match field {
+            "entry_name" => &self.entry_name,
+            "entry_votes" => &self.entry_votes,
+            "entry_raised" => &self.entry_raised,
+            "entry_category" => &self.entry_category,
+            "entry_picture" => &self.entry_picture,
+            "contest_raised" => &self.contest_raised,
+            "contest_goal" => &self.contest_goal,
+            "search_entries" => &self.search_entries,
+            "search_total" => &self.search_total,
+            "search_pager" => &self.search_pager,
+            "search_state" => &self.search_state,
+            _ => "",
+        }
+    }
+
+    /// Run every selector for `kind` against a page and report what each of them found
+    pub fn check(&self, kind: PageKind, html: &str) -> Vec<FieldCheck> {
+        let doc = Document::from(html);
+
+        FIELDS
+            .iter()
+            .filter(|field| field.page == kind)
+            .map(|field| {
+                let selector = self.get(field.name);
+                let selection = doc.select(selector);
+                let value = match field.name {
+                    "entry_picture" => selection.attr("src").map(|src| src.to_string()),
+                    // hidden inputs have no text, what they are called is what matters
+                    "search_state" => Some(joined(selection.iter().filter_map(|input| input.attr("name").map(|name| name.to_string())))),
+                    // every match one after the other, like the links of the pager
+                    _ => Some(joined(selection.iter().map(|node| node.text().split_whitespace().collect::<Vec<_>>().join(" ")))),
+                };
+
+                FieldCheck {
+                    field: field.name,
+                    selector: selector.to_string(),
+                    required: field.required,
+                    matches: selection.length(),
+                    value: value.filter(|value| !value.is_empty()),
+                }
+            })
+            .collect()
+    }
+}
+
+fn joined(values: impl Iterator<Item = String>) -> String {
+    values.filter(|value| !value.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+/// What one selector found on a page
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldCheck {
+    pub field: &'static str,
+    pub selector: String,
+    pub required: bool,
+    pub matches: usize,
+    pub value: Option<String>,
+}
+
+impl FieldCheck {
+    pub fn resolved(&self) -> bool {
+        self.matches > 0 && self.value.is_some()
+    }
+}
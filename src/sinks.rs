@@ -0,0 +1,321 @@
+//! Where the uploader puts the csv files. Which one is used is picked per deployment with
+//! `--upload-sink` (or `UPLOAD_SINK`):
+//!
+//! ```text
+//! gs://new-top-dog                 google cloud storage, the default
+//! file:///var/lib/new-top-dog      a local directory, to keep an archive or test uploads
+//! s3://new-top-dog                 an s3 compatible endpoint like aws or minio
+//! ```
+//!
+//! Every one of them can have a prefix that gets put in front of the file names,
+//! e.g. `gs://new-top-dog/neenah/`. The s3 sink takes its endpoint and region from
+//! `--s3-endpoint`/`S3_ENDPOINT` and `--s3-region`/`S3_REGION`, and its credentials
+//! from `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`.
+
+use std::{error::Error, fmt, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use futures::future::LocalBoxFuture;
+use ring::{digest, hmac};
+
+use crate::{
+    config::{self, ConfigError},
+    output::OutputDir,
+};
+
+// where the csv files have always gone
+pub const DEFAULT_UPLOAD_SINK: &str = "gs://new-top-dog";
+
+const DEFAULT_S3_REGION: &str = "us-east-1";
+
+/// Somewhere to put the files we upload
+pub trait Sink {
+    /// Where the files end up, for the logs
+    fn describe(&self) -> String;
+
+    /// Store `body` under `name`, replacing anything that is already there
+    fn put<'a>(&'a self, name: &'a str, body: Vec<u8>, mime_type: &'a str) -> LocalBoxFuture<'a, Result<(), Box<dyn Error>>>;
+}
+
+/// Which sink to use and where it puts things
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkConfig {
+    Gcs { bucket: String, prefix: String },
+    Local { dir: PathBuf },
+    S3(S3Config),
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct S3Config {
+    pub bucket: String,
+    pub prefix: String,
+    // e.g. http://localhost:9000 for minio, the buckets are addressed by path under it
+    pub endpoint: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+// the secret key stays out of the logs
+impl fmt::Debug for S3Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Config")
+            .field("bucket", &self.bucket)
+            .field("prefix", &self.prefix)
+            .field("endpoint", &self.endpoint)
+            .field("region", &self.region)
+            .field("access_key", &self.access_key)
+            .finish()
+    }
+}
+
+impl SinkConfig {
+    /// Read the sink from the settings, uploading to the `new-top-dog` bucket if none is set
+    pub fn from_settings() -> Result<SinkConfig, ConfigError> {
+        let url = config::setting("--upload-sink", "UPLOAD_SINK").unwrap_or_else(|| DEFAULT_UPLOAD_SINK.into());
+        let mut sink = SinkConfig::parse(&url)?;
+
+        if let SinkConfig::S3(s3) = &mut sink {
+            let region = config::setting("--s3-region", "S3_REGION").unwrap_or_else(|| DEFAULT_S3_REGION.into());
+            s3.endpoint = config::setting("--s3-endpoint", "S3_ENDPOINT")
+                .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region))
+                .trim_end_matches('/')
+                .to_string();
+            s3.region = region;
+            s3.access_key = std::env::var("AWS_ACCESS_KEY_ID").unwrap_or_default();
+            s3.secret_key = std::env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_default();
+        }
+
+        Ok(sink)
+    }
+
+    /// Parse a sink url, the s3 endpoint and credentials are left empty for `from_settings` to fill in
+    pub fn parse(url: &str) -> Result<SinkConfig, ConfigError> {
+        let invalid = |message: &str| ConfigError::Parse {
+            path: "--upload-sink".into(),
+            message: format!("{}: {:?}", message, url),
+        };
+
+        let (scheme, rest) = url
+            .split_once("://")
+            .ok_or_else(|| invalid("the upload sink has to be a gs://, s3:// or file:// url"))?;
+
+        if scheme == "file" {
+            if rest.is_empty() {
+                return Err(invalid("the upload sink needs a directory"));
+            }
+            return Ok(SinkConfig::Local { dir: PathBuf::from(rest) });
+        }
+
+        let (bucket, prefix) = match rest.split_once('/') {
+            Some((bucket, prefix)) => (bucket.to_string(), prefix.to_string()),
+            None => (rest.to_string(), String::new()),
+        };
+        if bucket.is_empty() {
+            return Err(invalid("the upload sink needs a bucket"));
+        }
+
+        match scheme {
+            "gs" => Ok(SinkConfig::Gcs { bucket, prefix }),
+            "s3" => Ok(SinkConfig::S3(S3Config {
+                bucket,
+                prefix,
+                endpoint: String::new(),
+                region: DEFAULT_S3_REGION.into(),
+                access_key: String::new(),
+                secret_key: String::new(),
+            })),
+            _ => Err(invalid("the upload sink has to be a gs://, s3:// or file:// url")),
+        }
+    }
+
+    pub fn build(self) -> Result<Box<dyn Sink>, Box<dyn Error>> {
+        Ok(match self {
+            SinkConfig::Gcs { bucket, prefix } => Box::new(GcsSink {
+                // the default client credentials, from GOOGLE_APPLICATION_CREDENTIALS
+                client: cloud_storage::Client::default(),
+                bucket,
+                prefix,
+            }),
+            SinkConfig::Local { dir } => Box::new(LocalSink { dir: OutputDir::new(dir) }),
+            SinkConfig::S3(s3) => {
+                if s3.access_key.is_empty() || s3.secret_key.is_empty() {
+                    return Err("the s3 upload sink needs AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY".into());
+                }
+                Box::new(S3Sink::new(s3)?)
+            }
+        })
+    }
+}
+
+pub struct GcsSink {
+    client: cloud_storage::Client,
+    bucket: String,
+    prefix: String,
+}
+
+impl Sink for GcsSink {
+    fn describe(&self) -> String {
+        format!("gs://{}/{}", self.bucket, self.prefix)
+    }
+
+    fn put<'a>(&'a self, name: &'a str, body: Vec<u8>, mime_type: &'a str) -> LocalBoxFuture<'a, Result<(), Box<dyn Error>>> {
+        Box::pin(async move {
+            let name = format!("{}{}", self.prefix, name);
+            self.client.object().create(&self.bucket, body, &name, mime_type).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Keeps every upload in a directory, written the same way as the output files
+pub struct LocalSink {
+    dir: OutputDir,
+}
+
+impl Sink for LocalSink {
+    fn describe(&self) -> String {
+        format!("file://{}", self.dir.root().display())
+    }
+
+    fn put<'a>(&'a self, name: &'a str, body: Vec<u8>, _mime_type: &'a str) -> LocalBoxFuture<'a, Result<(), Box<dyn Error>>> {
+        Box::pin(async move {
+            self.dir.write(name, &body)?;
+            Ok(())
+        })
+    }
+}
+
+/// Puts the files in an s3 compatible bucket with plain signed PUTs
+pub struct S3Sink {
+    http: reqwest::Client,
+    config: S3Config,
+    // what goes in the host header, with the port when it isn't the default
+    host: String,
+}
+
+impl S3Sink {
+    pub fn new(config: S3Config) -> Result<S3Sink, Box<dyn Error>> {
+        let endpoint = reqwest::Url::parse(&config.endpoint)?;
+        let host = match (endpoint.host_str(), endpoint.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(format!("the s3 endpoint has no host: {}", config.endpoint).into()),
+        };
+
+        Ok(S3Sink {
+            http: reqwest::Client::new(),
+            config,
+            host,
+        })
+    }
+}
+
+impl Sink for S3Sink {
+    fn describe(&self) -> String {
+        format!("s3://{}/{} at {}", self.config.bucket, self.config.prefix, self.config.endpoint)
+    }
+
+    fn put<'a>(&'a self, name: &'a str, body: Vec<u8>, mime_type: &'a str) -> LocalBoxFuture<'a, Result<(), Box<dyn Error>>> {
+        Box::pin(async move {
+            let path = uri_encode(&format!("/{}/{}{}", self.config.bucket, self.config.prefix, name));
+            let payload_hash = sha256_hex(&body);
+            let signer = SigV4 {
+                access_key: &self.config.access_key,
+                secret_key: &self.config.secret_key,
+                region: &self.config.region,
+                service: "s3",
+            };
+
+            let now = Utc::now();
+            let headers = vec![
+                ("content-type".to_string(), mime_type.to_string()),
+                ("host".to_string(), self.host.clone()),
+                ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+                ("x-amz-date".to_string(), amz_date(now)),
+            ];
+            let authorization = signer.authorization("PUT", &path, "", &headers, &payload_hash, now);
+
+            let mut request = self.http.put(format!("{}{}", self.config.endpoint, path)).body(body);
+            for (name, value) in headers.iter().filter(|(name, _)| name != "host") {
+                request = request.header(name.as_str(), value.as_str());
+            }
+
+            request.header("authorization", authorization).send().await?.error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+/// Signs requests with aws signature version 4
+pub struct SigV4<'a> {
+    pub access_key: &'a str,
+    pub secret_key: &'a str,
+    pub region: &'a str,
+    pub service: &'a str,
+}
+
+impl<'a> SigV4<'a> {
+    /// The authorization header for a request. `path` has to be uri encoded already and
+    /// `headers` are every header that gets signed, with lowercase names.
+    pub fn authorization(
+        &self,
+        method: &str,
+        path: &str,
+        query: &str,
+        headers: &[(String, String)],
+        payload_hash: &str,
+        at: DateTime<Utc>,
+    ) -> String {
+        let mut headers = headers.to_vec();
+        headers.sort();
+
+        let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+        let signed_headers = headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(";");
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method, path, query, canonical_headers, signed_headers, payload_hash
+        );
+
+        let date = at.format("%Y%m%d").to_string();
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date(at),
+            scope,
+            sha256_hex(canonical_request.as_bytes())
+        );
+
+        let key = [date.as_str(), self.region, self.service, "aws4_request"]
+            .iter()
+            .fold(format!("AWS4{}", self.secret_key).into_bytes(), |key, part| hmac_sha256(&key, part.as_bytes()));
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        )
+    }
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(digest::digest(&digest::SHA256, data))
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data).as_ref().to_vec()
+}
+
+fn amz_date(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// everything but the unreserved characters and the slashes between the parts of the path
+fn uri_encode(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
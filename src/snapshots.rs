@@ -0,0 +1,98 @@
+//! The latest crawl of each crawler kept in memory. When the crawlers and the api run in one
+//! process they all share one store, so the api and the goals crawler get every tick straight
+//! away instead of reading it back out of the files.
+
+use std::sync::{Arc, RwLock};
+
+use crate::{live::LiveState, ContestData, EntryData};
+
+/// What one crawler published in one tick
+#[derive(Debug, Clone)]
+pub struct Snapshot<T> {
+    // the store version it was published at, a bigger version is always newer
+    pub version: u64,
+    // the timestamp of the tick it is from
+    pub tick: i64,
+    pub data: Arc<Vec<T>>,
+}
+
+impl<T: Clone> Snapshot<T> {
+    fn new(version: u64, tick: i64, data: Vec<T>) -> Snapshot<T> {
+        Snapshot {
+            version,
+            tick,
+            data: Arc::new(data),
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.data.as_ref().clone()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Snapshots {
+    // goes up by one every time anything gets published
+    version: u64,
+    dogs: Option<Snapshot<EntryData>>,
+    leaderboard: Option<Snapshot<EntryData>>,
+    goals: Option<Snapshot<ContestData>>,
+}
+
+/// Cheap to clone, every clone sees the same snapshots
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotStore {
+    inner: Arc<RwLock<Snapshots>>,
+}
+
+impl SnapshotStore {
+    /// Publish the top dogs of a tick, sorted by votes like `top-dogs.json`, along with the
+    /// global leaderboard. Returns the version they got.
+    pub fn publish_dogs(&self, tick: i64, dogs: Vec<EntryData>, leaderboard: Vec<EntryData>) -> u64 {
+        let mut snapshots = self.inner.write().unwrap();
+        snapshots.version += 1;
+
+        snapshots.dogs = Some(Snapshot::new(snapshots.version, tick, dogs));
+        snapshots.leaderboard = Some(Snapshot::new(snapshots.version, tick, leaderboard));
+        snapshots.version
+    }
+
+    /// Publish the contest goals of a tick, returns the version they got
+    pub fn publish_goals(&self, tick: i64, goals: Vec<ContestData>) -> u64 {
+        let mut snapshots = self.inner.write().unwrap();
+        snapshots.version += 1;
+
+        snapshots.goals = Some(Snapshot::new(snapshots.version, tick, goals));
+        snapshots.version
+    }
+
+    /// The version of the newest snapshot, 0 when nothing has been published yet
+    pub fn version(&self) -> u64 {
+        self.inner.read().unwrap().version
+    }
+
+    pub fn dogs(&self) -> Option<Snapshot<EntryData>> {
+        self.inner.read().unwrap().dogs.clone()
+    }
+
+    pub fn leaderboard(&self) -> Option<Snapshot<EntryData>> {
+        self.inner.read().unwrap().leaderboard.clone()
+    }
+
+    pub fn goals(&self) -> Option<Snapshot<ContestData>> {
+        self.inner.read().unwrap().goals.clone()
+    }
+
+    /// Everything the live updates are worked out from, None until something has been published
+    pub fn live_state(&self) -> Option<LiveState> {
+        let snapshots = self.inner.read().unwrap();
+        if snapshots.version == 0 {
+            return None;
+        }
+
+        Some(LiveState {
+            dogs: snapshots.dogs.as_ref().map(Snapshot::to_vec).unwrap_or_default(),
+            goals: snapshots.goals.as_ref().map(Snapshot::to_vec).unwrap_or_default(),
+        })
+    }
+}
@@ -0,0 +1,233 @@
+//! The queue of files waiting to be uploaded. Every snapshot goes in to the spool directory
+//! before anything tries to upload it and only comes back out once the sink has taken it,
+//! so nothing is lost when the sink is down or the uploader gets restarted half way through.
+//!
+//! Snapshots that are the same as the last one of their kind don't go in to the spool at
+//! all, apart from a heartbeat every `--upload-heartbeat-hours` to show we are still alive.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use ring::digest;
+
+use crate::{config, output::OutputDir};
+
+#[derive(Debug, Clone)]
+pub struct Spool {
+    dir: OutputDir,
+}
+
+impl Spool {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Spool {
+        // the spool is written to even when the output files are turned off
+        Spool { dir: OutputDir::new(dir) }
+    }
+
+    /// The spool from `--spool-dir` or `SPOOL_DIR`, `spool` in the output directory if neither is set
+    pub fn from_settings(output: &OutputDir) -> Spool {
+        match config::setting("--spool-dir", "SPOOL_DIR") {
+            Some(dir) => Spool::new(dir),
+            None => Spool::new(output.path("spool")),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.dir.root()
+    }
+
+    /// Queue `contents` to be uploaded as `name`
+    pub fn enqueue(&self, name: &str, contents: &[u8]) -> io::Result<()> {
+        self.dir.write(name, contents).map(|_| ())
+    }
+
+    /// Everything waiting to be uploaded, oldest first. Files in directories under the spool,
+    /// like the exports, are named by their path in it, e.g. `export/top-dogs/schema.json`.
+    pub fn pending(&self) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        match pending_in(self.dir(), "", &mut names) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            result => result?,
+        }
+
+        names.sort_by_key(|name| (timestamp(name), name.clone()));
+        Ok(names)
+    }
+
+    pub fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.dir.path(name))
+    }
+
+    /// Take a file out of the queue once it has been uploaded
+    pub fn remove(&self, name: &str) -> io::Result<()> {
+        let path = self.dir.path(name);
+        fs::remove_file(&path)?;
+
+        // and the directories it was in once they are empty, remove_dir fails on the first one that isn't
+        for dir in path.ancestors().skip(1).take_while(|dir| *dir != self.dir()) {
+            if fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn pending_in(dir: &Path, prefix: &str, names: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // the temp files of writes that haven't finished yet start with a dot
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            pending_in(&entry.path(), &format!("{}{}/", prefix, name), names)?;
+        } else if file_type.is_file() {
+            names.push(format!("{}{}", prefix, name));
+        }
+    }
+
+    Ok(())
+}
+
+// the uploads are named like `top-dogs-1664000000.csv`, anything else sorts first
+fn timestamp(name: &str) -> i64 {
+    let stem = name.split('.').next().unwrap_or(name);
+
+    stem.rsplit('-').next().and_then(|timestamp| timestamp.parse().ok()).unwrap_or(0)
+}
+
+/// The content type to upload a file with
+pub fn mime_type(name: &str) -> &'static str {
+    match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => "text/csv",
+        Some("json") => "application/json",
+        Some("gz") => "application/gzip",
+        Some("parquet") => "application/vnd.apache.parquet",
+        _ => "application/octet-stream",
+    }
+}
+
+/// What to do with a snapshot that is about to be queued
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upload {
+    Changed,
+    // nothing changed, but nothing has gone up in so long that it goes up anyway
+    Heartbeat,
+    Unchanged,
+}
+
+/// Remembers what each kind of snapshot looked like the last time one was queued, so the same
+/// data doesn't go up every minute all night. It only lives in memory, so the first snapshot
+/// after a restart always goes up.
+#[derive(Debug, Default)]
+pub struct Dedupe {
+    // seconds, 0 for no heartbeat
+    heartbeat: i64,
+    // the hash of the last snapshot of each kind and when it was queued
+    last: HashMap<String, (String, i64)>,
+}
+
+impl Dedupe {
+    pub fn new(heartbeat_hours: i64) -> Dedupe {
+        Dedupe {
+            heartbeat: heartbeat_hours.max(0) * 60 * 60,
+            last: HashMap::new(),
+        }
+    }
+
+    /// With the heartbeat from `--upload-heartbeat-hours` or `UPLOAD_HEARTBEAT_HOURS`, off by default
+    pub fn from_settings() -> Dedupe {
+        Dedupe::new(config::parsed_setting("--upload-heartbeat-hours", "UPLOAD_HEARTBEAT_HOURS").unwrap_or(0))
+    }
+
+    /// Whether `contents`, about to be queued as `name` at `now`, should be uploaded
+    pub fn check(&mut self, name: &str, contents: &[u8], now: i64) -> Upload {
+        let hash = content_hash(name, contents);
+        let upload = match self.last.get(snapshot_kind(name)) {
+            Some((last, _)) if *last != hash => Upload::Changed,
+            Some((_, queued_at)) if self.heartbeat > 0 && now - queued_at >= self.heartbeat => Upload::Heartbeat,
+            Some(_) => Upload::Unchanged,
+            None => Upload::Changed,
+        };
+
+        if upload != Upload::Unchanged {
+            self.last.insert(snapshot_kind(name).to_string(), (hash, now));
+        }
+        upload
+    }
+
+    /// Forget the last snapshot of the kind `name` is, for when it never made it in to the spool
+    pub fn forget(&mut self, name: &str) {
+        self.last.remove(snapshot_kind(name));
+    }
+}
+
+// `top-dogs-1664000060.csv` is a `top-dogs`
+fn snapshot_kind(name: &str) -> &str {
+    let stem = name.split('.').next().unwrap_or(name);
+
+    match stem.rsplit_once('-') {
+        Some((kind, timestamp)) if timestamp.parse::<i64>().is_ok() => kind,
+        _ => stem,
+    }
+}
+
+/// A hash of what is in a snapshot. For csv files the `timestamp` column and the forecast
+/// columns are left out, they change every tick even when nothing else does.
+pub fn content_hash(name: &str, contents: &[u8]) -> String {
+    let mut hash = digest::Context::new(&digest::SHA256);
+
+    match csv_without_ignored(name, contents) {
+        Some(records) => {
+            for record in records {
+                for field in record.iter() {
+                    hash.update(field.as_bytes());
+                    // unit and record separators so moving text between fields changes the hash
+                    hash.update(b"\x1f");
+                }
+                hash.update(b"\x1e");
+            }
+        }
+        None => hash.update(contents),
+    }
+
+    hex::encode(hash.finish())
+}
+
+// the csv columns that move with the clock rather than the contests
+const IGNORED_COLUMNS: &[&str] = &["timestamp", "velocity_per_hour", "projected_total", "required_per_hour"];
+
+// the header and rows of a csv file without its ignored columns, None if it isn't csv
+fn csv_without_ignored(name: &str, contents: &[u8]) -> Option<Vec<Vec<String>>> {
+    if mime_type(name) != "text/csv" {
+        return None;
+    }
+
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).from_reader(contents);
+    let mut rows = reader.records();
+    let header = rows.next()?.ok()?;
+    let ignored: Vec<bool> = header.iter().map(|column| IGNORED_COLUMNS.contains(&column)).collect();
+
+    let without = |record: &csv::StringRecord| -> Vec<String> {
+        record
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !ignored.get(*i).copied().unwrap_or(false))
+            .map(|(_, field)| field.to_string())
+            .collect()
+    };
+
+    let mut records = vec![without(&header)];
+    for row in rows {
+        records.push(without(&row.ok()?));
+    }
+
+    Some(records)
+}
@@ -0,0 +1,173 @@
+//! Keeps the last good crawl of every contest around so one flaky contest page
+//! only makes that contest go stale instead of throwing away the whole tick.
+
+use std::{collections::HashMap, error::Error};
+
+use log::{error, warn};
+use serde::Serialize;
+
+use crate::{fetch::ContestTiming, scraper::ScrapeHealth, ContestData, EntryData};
+
+/// How one contest went in a tick, what the crawlers hand to `LastGood::merge`
+pub type ContestCrawl<T> = (ContestTiming, Result<Vec<T>, Box<dyn Error>>);
+
+/// Something a crawler produces for a contest that can be carried forward when the contest fails
+pub trait Crawled: Clone {
+    /// The page of the contest it belongs to
+    fn contest_page(&self) -> &str;
+    /// When it was crawled
+    fn crawled_at(&self) -> i64;
+    /// Flag it as carried forward from an earlier tick
+    fn mark_stale(&mut self, now: i64);
+}
+
+impl Crawled for EntryData {
+    fn contest_page(&self) -> &str {
+        &self.contest.page
+    }
+
+    fn crawled_at(&self) -> i64 {
+        self.timestamp
+    }
+
+    fn mark_stale(&mut self, now: i64) {
+        self.stale = true;
+        self.stale_age = now - self.timestamp;
+    }
+}
+
+impl Crawled for ContestData {
+    fn contest_page(&self) -> &str {
+        &self.contest.page
+    }
+
+    fn crawled_at(&self) -> i64 {
+        self.timestamp
+    }
+
+    fn mark_stale(&mut self, now: i64) {
+        self.stale = true;
+        self.stale_age = now - self.timestamp;
+    }
+}
+
+/// A contest that failed to crawl this tick
+#[derive(Debug, Clone, Serialize)]
+pub struct ContestError {
+    pub page: String,
+    pub attempts: u32,
+    pub error: String,
+    // when the data we carried forward instead was crawled,
+    // None when we never had good data for the contest
+    pub last_good: Option<i64>,
+}
+
+/// Which contests made it and which did not, written alongside the json outputs every tick
+#[derive(Debug, Clone, Serialize)]
+pub struct TickErrors {
+    pub timestamp: i64,
+    pub crawled: Vec<String>,
+    pub failed: Vec<ContestError>,
+    // whether the selectors still find everything they need to
+    pub health: ScrapeHealth,
+}
+
+impl TickErrors {
+    /// Add in how the scraping went, complaining loudly when the site looks to have changed
+    pub fn record_health(&mut self, crawler: &str, health: ScrapeHealth) {
+        if !health.healthy {
+            error!(
+                "Scrape is unhealthy, required selectors matched nothing; crawler={}; fields={:?}; hint=run `crawler check-selectors` against a page",
+                crawler, health.missing,
+            );
+        }
+
+        self.health = health;
+    }
+
+    /// Nothing came back fresh, so there is no point in writing out the outputs
+    pub fn nothing_crawled(&self) -> bool {
+        self.crawled.is_empty()
+    }
+}
+
+/// The last good results of every contest
+#[derive(Debug, Clone)]
+pub struct LastGood<T> {
+    by_page: HashMap<String, Vec<T>>,
+}
+
+impl<T> Default for LastGood<T> {
+    fn default() -> LastGood<T> {
+        LastGood { by_page: HashMap::new() }
+    }
+}
+
+impl<T: Crawled> LastGood<T> {
+    /// Start from what a previous run left behind, e.g. the last `top-dogs.json`
+    pub fn seed(items: Vec<T>) -> LastGood<T> {
+        let mut last_good = LastGood::default();
+        for item in items {
+            last_good.by_page.entry(item.contest_page().to_string()).or_insert_with(Vec::new).push(item);
+        }
+
+        last_good
+    }
+
+    /// When the data we have for a contest was crawled
+    pub fn crawled_at(&self, page: &str) -> Option<i64> {
+        self.by_page.get(page)?.iter().map(Crawled::crawled_at).max()
+    }
+
+    /// The data we have for contests that closed and are done being crawled, it is
+    /// not stale, there is just nothing newer to get
+    pub fn closed(&self, pages: &[String]) -> Vec<T> {
+        pages.iter().filter_map(|page| self.by_page.get(page)).flatten().cloned().collect()
+    }
+
+    /// Combine the results of a tick, falling back to the last good data for every
+    /// contest that failed and remembering the fresh data for the next tick
+    pub fn merge(&mut self, now: i64, crawls: Vec<ContestCrawl<T>>) -> (Vec<T>, TickErrors) {
+        let mut results = Vec::new();
+        let mut errors = TickErrors {
+            timestamp: now,
+            crawled: Vec::new(),
+            failed: Vec::new(),
+            health: ScrapeHealth::default(),
+        };
+
+        for (timing, ret) in crawls {
+            match ret {
+                Ok(res) => {
+                    results.extend(res.iter().cloned());
+                    self.by_page.insert(timing.page.clone(), res);
+                    errors.crawled.push(timing.page);
+                }
+                Err(e) => {
+                    error!("Unable to crawl contest; contest={}; attempts={}; error={}", timing.page, timing.attempts, e);
+
+                    let last_good = self.crawled_at(&timing.page);
+                    match self.by_page.get(&timing.page) {
+                        Some(carried) => {
+                            warn!("Using stale data for contest; contest={}; age={}", timing.page, now - last_good.unwrap_or(now));
+                            results.extend(carried.iter().cloned().map(|mut item| {
+                                item.mark_stale(now);
+                                item
+                            }));
+                        }
+                        None => warn!("No good data to fall back on for contest; contest={}", timing.page),
+                    }
+
+                    errors.failed.push(ContestError {
+                        page: timing.page,
+                        attempts: timing.attempts,
+                        error: e.to_string(),
+                        last_good,
+                    });
+                }
+            }
+        }
+
+        (results, errors)
+    }
+}
@@ -0,0 +1,977 @@
+//! The web server the front end gets the leaderboards from
+//!
+//! It runs on actix, so it needs its own actix system and can't just be
+//! spawned on the tokio runtime the crawlers run on
+
+use std::{cmp::Reverse, collections::HashSet, error::Error, fmt::Display, str::FromStr, sync::Mutex, time::Duration};
+
+use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix_web::{dev::Server, error::BlockingError, get, web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_web_actors::ws;
+use actix_cors::Cors;
+use chrono::Utc;
+use futures::{channel::mpsc, StreamExt};
+use serde::{
+    de::{self, DeserializeOwned},
+    Deserialize, Deserializer, Serialize,
+};
+use tokio::sync::oneshot;
+
+use log::{error, info, warn};
+
+use crate::{
+    events::{Event, EventKind},
+    forecast::{self, Forecast, ForecastSettings},
+    history::{self, ContestRecord, Downsample, EntryRecord, EventFilter, History},
+    live::{self, LiveState, LiveUpdate},
+    output::OutputDir,
+    schedule::ContestStatus,
+    seasons::{self, Comparison},
+    snapshots::{Snapshot, SnapshotStore},
+    tasks::Shutdown,
+    Contest, ContestData, Contests, EntryData, Money, GLOBAL_LEADERBOARD_SIZE,
+};
+
+// when no time range is given we show the last day
+const DEFAULT_RANGE_SECONDS: i64 = 24 * 60 * 60;
+
+#[derive(Debug, Deserialize)]
+struct RangeQuery {
+    // unix timestamps, both ends are inclusive
+    #[serde(default, deserialize_with = "from_str")]
+    from: Option<i64>,
+    #[serde(default, deserialize_with = "from_str")]
+    to: Option<i64>,
+    // minute, hour or day
+    bucket: Option<Downsample>,
+}
+
+// Everything in a query string is a string, and once RangeQuery is flattened into another
+// query serde no longer knows to parse the numbers out of them so it has to be done here
+fn from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| value.parse().map_err(de::Error::custom))
+        .transpose()
+}
+
+impl RangeQuery {
+    fn range(&self) -> (i64, i64) {
+        let to = self.to.unwrap_or_else(|| Utc::now().timestamp());
+        let from = self.from.unwrap_or(to - DEFAULT_RANGE_SECONDS);
+
+        (from, to)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct TimeSeries<T> {
+    from: i64,
+    to: i64,
+    bucket: Option<Downsample>,
+    points: Vec<T>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+fn history_error(e: impl Display) -> HttpResponse {
+    error!("Unable to read history; error={}", e);
+    HttpResponse::InternalServerError().json(ErrorBody {
+        error: "unable to read history".into(),
+    })
+}
+
+// The history database the handlers read from. sqlite blocks, so every query gets its own
+// connection on the blocking thread pool instead of holding up the other requests on the worker
+#[derive(Debug, Clone)]
+struct HistoryDb {
+    path: String,
+}
+
+impl HistoryDb {
+    async fn read<T, F>(&self, query: F) -> Result<T, HttpResponse>
+    where
+        T: Send + 'static,
+        F: FnOnce(&History) -> Result<T, history::Error> + Send + 'static,
+    {
+        let path = self.path.clone();
+
+        web::block(move || query(&History::open(path)?)).await.map_err(|e| match e {
+            BlockingError::Error(e) => history_error(e),
+            canceled => history_error(canceled),
+        })
+    }
+}
+
+/// What `/dogs`, `/goals` and `/leaderboard` respond with
+#[derive(Debug, Serialize)]
+struct SnapshotResponse<T> {
+    // when the newest thing in `data` was crawled, None if there is no data at all
+    last_updated: Option<i64>,
+    data: Vec<T>,
+}
+
+impl<T> SnapshotResponse<T> {
+    fn new(data: Vec<T>, timestamp: impl Fn(&T) -> i64) -> SnapshotResponse<T> {
+        SnapshotResponse {
+            last_updated: data.iter().map(timestamp).max(),
+            data,
+        }
+    }
+}
+
+// Take the latest crawl from the snapshot store when the crawler runs in this process,
+// otherwise from the file it writes
+fn latest<T: Clone + DeserializeOwned>(snapshot: Option<Snapshot<T>>, output: &OutputDir, file: &str) -> Result<Vec<T>, HttpResponse> {
+    match snapshot {
+        Some(snapshot) => Ok(snapshot.to_vec()),
+        None => read_snapshot(output, file),
+    }
+}
+
+// Read one of the files the crawlers write, if the crawler hasn't written it yet there
+// is nothing we can give back so let the caller know to try again later
+fn read_snapshot<T: DeserializeOwned>(output: &OutputDir, file: &str) -> Result<Vec<T>, HttpResponse> {
+    let content = match output.read_to_string(file) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!("Unable to read file; file={}; error={}", file, e);
+            return Err(unavailable("no crawl has completed yet"));
+        }
+    };
+
+    serde_json::from_str(&content).map_err(|e| {
+        error!("Unable to parse file; file={}; error={}", file, e);
+        unavailable("the latest crawl could not be read")
+    })
+}
+
+fn unavailable(reason: &str) -> HttpResponse {
+    HttpResponse::ServiceUnavailable()
+        .header("Retry-After", "60")
+        .json(ErrorBody {
+            error: reason.into(),
+        })
+}
+
+fn not_found(error: String) -> HttpResponse {
+    HttpResponse::NotFound().json(ErrorBody { error })
+}
+
+#[derive(Debug, Deserialize)]
+struct SeasonQuery {
+    // a past season, the current one by default
+    season: Option<String>,
+}
+
+// The contests of the season asked for if it is a past one, those come from the history
+// instead of the latest crawl. Ok(None) is the current season.
+fn past_season(contests: &Contests, season: Option<&str>) -> Result<Option<Contests>, HttpResponse> {
+    let season = match season {
+        Some(season) if season != contests.current_season() => season,
+        _ => return Ok(None),
+    };
+
+    match contests.season(season) {
+        Some(past) => Ok(Some(past)),
+        None => Err(not_found(format!("there is no season {:?}", season))),
+    }
+}
+
+// how the contests of a season ended up, from the last crawl of each of them
+fn season_goals(season: &Contests, history: &History) -> Result<Vec<ContestData>, history::Error> {
+    let mut goals = Vec::new();
+    for contest in season.get_all() {
+        if let Some(record) = history.latest_contest(&contest.page)? {
+            goals.push(ContestData {
+                contest,
+                goal: record.goal,
+                raised: record.raised,
+                total_entries: record.total_entries,
+                champ_day: record.champ_day,
+                timestamp: record.timestamp,
+                stale: false,
+                stale_age: 0,
+                forecast: None,
+            });
+        }
+    }
+
+    Ok(goals)
+}
+
+// the dogs of a season as the last crawl of each contest had them, most votes first
+fn season_dogs(season: &Contests, history: &History) -> Result<Vec<EntryData>, history::Error> {
+    let mut dogs = Vec::new();
+    for contest in season.get_all() {
+        dogs.extend(history.latest_entries(&contest.page)?.into_iter().map(|record| entry_from_record(record, season)));
+    }
+    dogs.sort_by_key(|dog| Reverse(dog.votes));
+
+    Ok(dogs)
+}
+
+#[get("/goals")]
+async fn get_goals(
+    query: web::Query<SeasonQuery>,
+    contests: web::Data<Contests>,
+    history: web::Data<HistoryDb>,
+    output: web::Data<OutputDir>,
+    store: web::Data<SnapshotStore>,
+) -> HttpResponse {
+    info!("handling goals; season={:?}", query.season);
+
+    match past_season(&contests, query.season.as_deref()) {
+        Ok(None) => {}
+        Ok(Some(season)) => {
+            return match history.read(move |history| season_goals(&season, history)).await {
+                Ok(goals) => HttpResponse::Ok().json(SnapshotResponse::new(goals, |c| c.timestamp)),
+                Err(response) => response,
+            }
+        }
+        Err(response) => return response,
+    }
+
+    match latest::<ContestData>(store.goals(), &output, "contest-goals.json") {
+        Ok(goals) => HttpResponse::Ok().json(SnapshotResponse::new(goals, |c| c.timestamp)),
+        Err(response) => response,
+    }
+}
+
+#[get("/dogs")]
+async fn get_dogs(
+    query: web::Query<SeasonQuery>,
+    contests: web::Data<Contests>,
+    history: web::Data<HistoryDb>,
+    output: web::Data<OutputDir>,
+    store: web::Data<SnapshotStore>,
+) -> HttpResponse {
+    info!("handling dogs; season={:?}", query.season);
+
+    match past_season(&contests, query.season.as_deref()) {
+        Ok(None) => {}
+        Ok(Some(season)) => {
+            return match history.read(move |history| season_dogs(&season, history)).await {
+                Ok(dogs) => HttpResponse::Ok().json(SnapshotResponse::new(dogs, |d| d.timestamp)),
+                Err(response) => response,
+            }
+        }
+        Err(response) => return response,
+    }
+
+    match latest::<EntryData>(store.dogs(), &output, "top-dogs.json") {
+        Ok(dogs) => HttpResponse::Ok().json(SnapshotResponse::new(dogs, |d| d.timestamp)),
+        Err(response) => response,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LeaderboardQuery {
+    // rebuild the leaderboard as it was at this unix timestamp
+    at: Option<i64>,
+    // or as a past season ended
+    season: Option<String>,
+}
+
+#[get("/leaderboard")]
+async fn get_leaderboard(
+    query: web::Query<LeaderboardQuery>,
+    history: web::Data<HistoryDb>,
+    contests: web::Data<Contests>,
+    output: web::Data<OutputDir>,
+    store: web::Data<SnapshotStore>,
+) -> HttpResponse {
+    info!("handling leaderboard; at={:?}; season={:?}", query.at, query.season);
+
+    let at = match query.at {
+        Some(at) => at,
+        None => {
+            // a past season is rebuilt from how its contests ended
+            match past_season(&contests, query.season.as_deref()) {
+                Ok(None) => {}
+                Ok(Some(season)) => {
+                    return match history.read(move |history| season_dogs(&season, history)).await {
+                        Ok(dogs) => {
+                            let leaderboard: Vec<EntryData> = dogs.into_iter().take(GLOBAL_LEADERBOARD_SIZE).collect();
+                            HttpResponse::Ok().json(SnapshotResponse::new(leaderboard, |d| d.timestamp))
+                        }
+                        Err(response) => response,
+                    }
+                }
+                Err(response) => return response,
+            }
+
+            return match latest::<EntryData>(store.leaderboard(), &output, "global-leaderboard.json") {
+                Ok(dogs) => HttpResponse::Ok().json(SnapshotResponse::new(dogs, |d| d.timestamp)),
+                Err(response) => response,
+            };
+        }
+    };
+
+    let records = match history.read(move |history| history.leaderboard_at(at)).await {
+        Ok(records) => records,
+        Err(response) => return response,
+    };
+
+    let leaderboard: Vec<EntryData> = records
+        .into_iter()
+        .take(GLOBAL_LEADERBOARD_SIZE)
+        .map(|record| entry_from_record(record, &contests))
+        .collect();
+
+    HttpResponse::Ok().json(SnapshotResponse::new(leaderboard, |d| d.timestamp))
+}
+
+// turn a row from the history back into what the leaderboard json looks like
+fn entry_from_record(record: EntryRecord, contests: &Contests) -> EntryData {
+    let contest = contests.from_page(&record.contest_page).unwrap_or_else(|| Contest {
+        display_name: record.display_name.clone(),
+        page: record.contest_page.clone(),
+        ..Contest::default()
+    });
+
+    EntryData {
+        dog: record.dog,
+        votes: record.votes,
+        raised: record.raised,
+        contest,
+        category: record.category,
+        page: record.entry_url,
+        picture: record.picture,
+        timestamp: record.timestamp,
+        stale: false,
+        stale_age: 0,
+    }
+}
+
+/// A contest in `/contests`, with whether it is open right now
+#[derive(Debug, Serialize)]
+struct ContestSummary {
+    #[serde(flatten)]
+    contest: Contest,
+    status: ContestStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContestsQuery {
+    // only the contests that are upcoming, live or closed
+    status: Option<ContestStatus>,
+    // the contests of a past season, the current one by default
+    season: Option<String>,
+}
+
+#[get("/contests")]
+async fn get_contests(query: web::Query<ContestsQuery>, contests: web::Data<Contests>) -> HttpResponse {
+    info!("handling contests; status={:?}; season={:?}", query.status, query.season);
+
+    let season = query.season.as_deref().unwrap_or_else(|| contests.current_season());
+    let contests = match contests.season(season) {
+        Some(contests) => contests,
+        None => return not_found(format!("there is no season {:?}", season)),
+    };
+
+    let now = Utc::now().timestamp();
+    let summaries: Vec<ContestSummary> = contests
+        .get_all()
+        .into_iter()
+        .map(|contest| ContestSummary {
+            status: contest.status(now),
+            contest,
+        })
+        .filter(|summary| query.status.is_none_or(|status| summary.status == status))
+        .collect();
+
+    HttpResponse::Ok().json(summaries)
+}
+
+/// A season in `/seasons`
+#[derive(Debug, Serialize)]
+struct SeasonSummary {
+    name: String,
+    // the season being crawled
+    current: bool,
+    // live while any of its contests are, upcoming while any are still to start
+    status: ContestStatus,
+    // the pages of its contests
+    contests: Vec<String>,
+}
+
+#[get("/seasons")]
+async fn get_seasons(contests: web::Data<Contests>) -> HttpResponse {
+    info!("handling seasons;");
+
+    let now = Utc::now().timestamp();
+    let seasons: Vec<SeasonSummary> = contests
+        .seasons()
+        .iter()
+        .map(|name| {
+            let season = contests.season(name).map(|season| season.get_all()).unwrap_or_default();
+            let statuses: Vec<ContestStatus> = season.iter().map(|contest| contest.status(now)).collect();
+            let status = [ContestStatus::Live, ContestStatus::Upcoming]
+                .iter()
+                .copied()
+                .find(|status| statuses.contains(status))
+                .unwrap_or(ContestStatus::Closed);
+
+            SeasonSummary {
+                name: name.clone(),
+                current: name == contests.current_season(),
+                status,
+                contests: season.into_iter().map(|contest| contest.page).collect(),
+            }
+        })
+        .collect();
+
+    HttpResponse::Ok().json(seasons)
+}
+
+#[derive(Debug, Deserialize)]
+struct CompareQuery {
+    // the current season by default
+    season: Option<String>,
+    // the season before `season` by default
+    against: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CompareResponse {
+    season: String,
+    against: String,
+    shelters: Vec<Comparison>,
+}
+
+#[get("/seasons/compare")]
+async fn get_season_comparison(
+    query: web::Query<CompareQuery>,
+    contests: web::Data<Contests>,
+    history: web::Data<HistoryDb>,
+) -> HttpResponse {
+    let season = query.season.clone().unwrap_or_else(|| contests.current_season().to_string());
+    let against = match query.against.clone().or_else(|| contests.previous_season(&season).map(String::from)) {
+        Some(against) => against,
+        None => return not_found(format!("there is no season before {:?} to compare it with", season)),
+    };
+    info!("handling season comparison; season={:?}; against={:?}", season, against);
+
+    if let Some(unknown) = [&season, &against].iter().find(|name| contests.season(name).is_none()) {
+        return not_found(format!("there is no season {:?}", unknown));
+    }
+
+    let compared = {
+        let (contests, season, against) = (contests.clone(), season.clone(), against.clone());
+        history.read(move |history| seasons::compare(&contests, history, &season, &against)).await
+    };
+
+    match compared {
+        Ok(shelters) => HttpResponse::Ok().json(CompareResponse { season, against, shelters }),
+        Err(response) => response,
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ShelterResponse {
+    shelter: String,
+    // oldest first, each next to the season before it
+    seasons: Vec<Comparison>,
+}
+
+#[get("/shelters/{shelter}/seasons")]
+async fn get_shelter_seasons(
+    path: web::Path<String>,
+    contests: web::Data<Contests>,
+    history: web::Data<HistoryDb>,
+) -> HttpResponse {
+    let shelter = path.into_inner();
+    info!("handling shelter seasons; shelter={}", shelter);
+
+    if !contests.get_all().iter().any(|contest| contest.shelter_key() == shelter) {
+        return not_found(format!("there is no shelter {:?}", shelter));
+    }
+
+    let seasons = {
+        let (contests, shelter) = (contests.clone(), shelter.clone());
+        history.read(move |history| seasons::shelter_seasons(&contests, history, &shelter)).await
+    };
+
+    match seasons {
+        Ok(seasons) => HttpResponse::Ok().json(ShelterResponse { shelter, seasons }),
+        Err(response) => response,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DogHistoryQuery {
+    #[serde(flatten)]
+    range: RangeQuery,
+    // the page of the contest the dog is in, entry ids can come back in another
+    // contest or season so it is the one the dog was last crawled in by default
+    contest: Option<String>,
+}
+
+#[get("/dogs/{entry}/history")]
+async fn get_dog_history(
+    path: web::Path<String>,
+    query: web::Query<DogHistoryQuery>,
+    history: web::Data<HistoryDb>,
+) -> HttpResponse {
+    let entry = path.into_inner();
+    let (from, to) = query.range.range();
+    info!(
+        "handling dog history; entry={}; contest={:?}; from={}; to={}; bucket={:?}",
+        entry, query.contest, from, to, query.range.bucket
+    );
+
+    let (contest, bucket) = (query.contest.clone(), query.range.bucket);
+    let points = history
+        .read(move |history| {
+            let contest = match contest {
+                Some(contest) => Some(contest),
+                None => history.latest_entry_contest(&entry)?,
+            };
+
+            match contest {
+                Some(contest) => history.entry_history(&contest, &entry, from, to, bucket),
+                None => Ok(Vec::new()),
+            }
+        })
+        .await;
+
+    match points {
+        Ok(points) => HttpResponse::Ok().json(TimeSeries::<EntryRecord> {
+            from,
+            to,
+            bucket,
+            points,
+        }),
+        Err(response) => response,
+    }
+}
+
+#[get("/contests/{page}/history")]
+async fn get_contest_history(
+    path: web::Path<String>,
+    query: web::Query<RangeQuery>,
+    history: web::Data<HistoryDb>,
+) -> HttpResponse {
+    let page = path.into_inner();
+    let (from, to) = query.range();
+    info!("handling contest history; page={}; from={}; to={}; bucket={:?}", page, from, to, query.bucket);
+
+    let bucket = query.bucket;
+    match history.read(move |history| history.contest_history(&page, from, to, bucket)).await {
+        Ok(points) => HttpResponse::Ok().json(TimeSeries::<ContestRecord> {
+            from,
+            to,
+            bucket,
+            points,
+        }),
+        Err(response) => response,
+    }
+}
+
+/// What `/contests/{page}/forecast` responds with
+#[derive(Debug, Serialize)]
+struct ForecastResponse {
+    contest: String,
+    display_name: String,
+    goal: Money,
+    // champ day money included, as of the last crawl
+    raised: Money,
+    // when the last crawl was
+    last_updated: i64,
+    // how far back the pace is measured
+    window_hours: i64,
+    #[serde(flatten)]
+    forecast: Forecast,
+}
+
+#[get("/contests/{page}/forecast")]
+async fn get_contest_forecast(
+    path: web::Path<String>,
+    contests: web::Data<Contests>,
+    history: web::Data<HistoryDb>,
+    settings: web::Data<ForecastSettings>,
+) -> HttpResponse {
+    let page = path.into_inner();
+    info!("handling contest forecast; page={}", page);
+
+    // the end date is only in the contests file
+    let contest = match contests.from_page(&page) {
+        Some(contest) => contest,
+        None => return not_found(format!("there is no contest with the page {:?}", page)),
+    };
+
+    let now = Utc::now().timestamp();
+    let from = now - settings.window_seconds();
+    let records = match history.read(move |history| history.contest_history(&page, from, now, None)).await {
+        Ok(records) => records,
+        Err(response) => return response,
+    };
+
+    let last = match records.last() {
+        Some(last) => last,
+        None => return unavailable("the contest has not been crawled recently"),
+    };
+
+    HttpResponse::Ok().json(ForecastResponse {
+        contest: contest.page,
+        display_name: contest.display_name,
+        goal: last.goal,
+        raised: last.raised + last.champ_day,
+        last_updated: last.timestamp,
+        window_hours: settings.window_hours,
+        forecast: forecast::forecast(&forecast::totals(&records), last.goal, contest.ends_at, now),
+    })
+}
+
+// how many events `/events` gives back when no limit is asked for, and the most it ever does
+const DEFAULT_EVENTS_LIMIT: usize = 100;
+const MAX_EVENTS_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    // events aren't bucketed, so a bucket in it is left alone
+    #[serde(flatten)]
+    range: RangeQuery,
+    // only the events of this contest page
+    contest: Option<String>,
+    // new_leader, overtake, entered_top, left_top or vote_jump
+    kind: Option<EventKind>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct EventsResponse {
+    from: i64,
+    to: i64,
+    events: Vec<Event>,
+}
+
+#[get("/events")]
+async fn get_events(query: web::Query<EventsQuery>, history: web::Data<HistoryDb>) -> HttpResponse {
+    let (from, to) = query.range.range();
+    info!("handling events; from={}; to={}; contest={:?}; kind={:?}", from, to, query.contest, query.kind);
+
+    let filter = EventFilter {
+        from,
+        to,
+        contest: query.contest.clone(),
+        kind: query.kind,
+        limit: query.limit.unwrap_or(DEFAULT_EVENTS_LIMIT).min(MAX_EVENTS_LIMIT),
+    };
+
+    match history.read(move |history| history.events(&filter)).await {
+        Ok(events) => HttpResponse::Ok().json(EventsResponse { from, to, events }),
+        Err(response) => response,
+    }
+}
+
+// how often we look for a new crawl to push to the live subscribers
+const LIVE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// the kind of update and the update as json
+type LiveMessage = (&'static str, String);
+
+struct Subscriber {
+    // only send updates about these contest pages, or everything if None
+    contests: Option<HashSet<String>>,
+    sender: mpsc::UnboundedSender<LiveMessage>,
+}
+
+impl Subscriber {
+    // returns false once the subscriber has gone away
+    fn send(&self, update: &LiveUpdate) -> bool {
+        let wanted = match (&self.contests, update.contest()) {
+            (Some(contests), Some(contest)) => contests.contains(contest),
+            _ => true,
+        };
+
+        if !wanted {
+            return true;
+        }
+
+        match serde_json::to_string(update) {
+            Ok(msg) => self.sender.unbounded_send((update.kind(), msg)).is_ok(),
+            Err(e) => {
+                error!("Unable to serialize live update; error={}", e);
+                true
+            }
+        }
+    }
+}
+
+// Keeps track of the last crawl and everyone listening for changes to it
+#[derive(Default)]
+struct Broadcaster {
+    state: Option<LiveState>,
+    subscribers: Vec<Subscriber>,
+}
+
+impl Broadcaster {
+    fn subscribe(&mut self, contests: Option<HashSet<String>>) -> mpsc::UnboundedReceiver<LiveMessage> {
+        let (sender, receiver) = mpsc::unbounded();
+        let subscriber = Subscriber { contests, sender };
+
+        if let Some(state) = &self.state {
+            for update in state.initial_updates() {
+                subscriber.send(&update);
+            }
+        }
+
+        self.subscribers.push(subscriber);
+        info!("live subscriber connected; subscribers={}", self.subscribers.len());
+
+        receiver
+    }
+
+    fn publish(&mut self, state: LiveState) {
+        // subscribers that went away while nothing changed would otherwise stick around until the next update
+        self.subscribers.retain(|subscriber| !subscriber.sender.is_closed());
+
+        if let Some(previous) = &self.state {
+            let updates = live::diff(previous, &state);
+
+            if !updates.is_empty() {
+                info!("pushing live updates; n={}; subscribers={}", updates.len(), self.subscribers.len());
+                self.subscribers.retain(|subscriber| updates.iter().all(|update| subscriber.send(update)));
+            }
+        }
+
+        self.state = Some(state);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LiveQuery {
+    // comma separated contest pages to get updates for, everything if not given
+    contests: Option<String>,
+}
+
+impl LiveQuery {
+    fn contests(&self) -> Option<HashSet<String>> {
+        self.contests.as_ref().map(|contests| {
+            contests
+                .split(',')
+                .map(|page| page.trim().to_string())
+                .filter(|page| !page.is_empty())
+                .collect()
+        })
+    }
+}
+
+struct LiveSession {
+    updates: Option<mpsc::UnboundedReceiver<LiveMessage>>,
+}
+
+impl Actor for LiveSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(updates) = self.updates.take() {
+            ctx.add_stream(updates);
+        }
+    }
+}
+
+impl StreamHandler<LiveMessage> for LiveSession {
+    fn handle(&mut self, (_, msg): LiveMessage, ctx: &mut Self::Context) {
+        ctx.text(msg);
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for LiveSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(bytes)) => ctx.pong(&bytes),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Err(e) => {
+                warn!("live websocket error; error={}", e);
+                ctx.stop();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn live_error(e: impl Display) -> HttpResponse {
+    error!("Unable to subscribe to live updates; error={}", e);
+    HttpResponse::InternalServerError().json(ErrorBody {
+        error: "unable to subscribe to live updates".into(),
+    })
+}
+
+#[get("/live")]
+async fn get_live_ws(
+    req: HttpRequest,
+    stream: web::Payload,
+    query: web::Query<LiveQuery>,
+    broadcaster: web::Data<Mutex<Broadcaster>>,
+) -> Result<HttpResponse, actix_web::Error> {
+    info!("handling live websocket; contests={:?}", query.contests);
+
+    let updates = match broadcaster.lock() {
+        Ok(mut broadcaster) => broadcaster.subscribe(query.contests()),
+        Err(e) => return Ok(live_error(e)),
+    };
+    ws::start(LiveSession { updates: Some(updates) }, &req, stream)
+}
+
+#[get("/live/events")]
+async fn get_live_events(
+    query: web::Query<LiveQuery>,
+    broadcaster: web::Data<Mutex<Broadcaster>>,
+) -> HttpResponse {
+    info!("handling live events; contests={:?}", query.contests);
+
+    let updates = match broadcaster.lock() {
+        Ok(mut broadcaster) => broadcaster.subscribe(query.contests()),
+        Err(e) => return live_error(e),
+    };
+    let events = updates.map(|(kind, msg)| {
+        Ok::<_, actix_web::Error>(web::Bytes::from(format!("event: {}\ndata: {}\n\n", kind, msg)))
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(events)
+}
+
+/// Bind the server and start serving, this has to be called from inside an actix system.
+///
+/// The crawls come out of `store` once the crawlers in this process have published to it,
+/// until then (or when the crawlers run somewhere else) they are read from the files.
+///
+/// When `handle_signals` is false the server won't stop on ctrl-c/SIGTERM
+/// by itself and whoever started it has to stop it through the returned handle.
+pub fn start(contests: Contests, store: SnapshotStore, handle_signals: bool) -> std::io::Result<Server> {
+    let contests = web::Data::new(contests);
+    let store = web::Data::new(store);
+
+    // open it once here so it is migrated before the first request comes in
+    let path = History::default_path();
+    let history = match History::open(&path) {
+        Ok(_) => web::Data::new(HistoryDb { path }),
+        Err(e) => {
+            error!("Unable to open history database; error={}", e);
+            return Err(std::io::Error::other(e));
+        }
+    };
+
+    let output = web::Data::new(OutputDir::from_settings());
+    info!("reading files; output_dir={}", output.root().display());
+
+    let forecast_settings = web::Data::new(ForecastSettings::from_settings());
+
+    let broadcaster = web::Data::new(Mutex::new(Broadcaster::default()));
+    let poller = broadcaster.clone();
+    let polled = output.clone();
+    let polled_store = store.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(LIVE_POLL_INTERVAL);
+        // the last store version that got pushed out
+        let mut pushed = 0;
+        loop {
+            interval.tick().await;
+
+            let version = polled_store.version();
+            let state = if version == 0 {
+                LiveState::read(&polled)
+            } else if version != pushed {
+                pushed = version;
+                polled_store.live_state()
+            } else {
+                // nothing new has been published since the last poll
+                None
+            };
+
+            if let Some(state) = state {
+                poller.lock().unwrap().publish(state);
+            }
+        }
+    });
+
+    let addr = "0.0.0.0:8080";
+    info!("started server; addr={}", addr);
+    let server = HttpServer::new(
+        move || {
+            let cors = Cors::permissive();
+
+            App::new()
+                .wrap(cors)
+                .app_data(contests.clone())
+                .app_data(history.clone())
+                .app_data(broadcaster.clone())
+                .app_data(output.clone())
+                .app_data(store.clone())
+                .app_data(forecast_settings.clone())
+                .service(get_goals)
+                .service(get_dogs)
+                .service(get_leaderboard)
+                .service(get_contests)
+                .service(get_seasons)
+                .service(get_season_comparison)
+                .service(get_shelter_seasons)
+                .service(get_dog_history)
+                .service(get_contest_history)
+                .service(get_contest_forecast)
+                .service(get_events)
+                .service(get_live_ws)
+                .service(get_live_events)
+        }
+    )
+        .bind(addr)?;
+
+    let server = if handle_signals {
+        server
+    } else {
+        server.disable_signals()
+    };
+
+    Ok(server.run())
+}
+
+/// Run the server on its own thread with its own actix system until we are told to shut down
+pub async fn run(contests: Contests, store: SnapshotStore, mut shutdown: Shutdown) -> Result<(), Box<dyn Error>> {
+    let (server_tx, server_rx) = oneshot::channel::<Server>();
+    let (done_tx, mut done_rx) = oneshot::channel::<std::io::Result<()>>();
+
+    std::thread::spawn(move || {
+        let result = actix_web::rt::System::new("api").block_on(async move {
+            let server = start(contests, store, false)?;
+            let _ = server_tx.send(server.clone());
+            server.await
+        });
+
+        let _ = done_tx.send(result);
+    });
+
+    tokio::select! {
+        result = &mut done_rx => {
+            return match result {
+                Ok(result) => result.map_err(|e| e.into()),
+                Err(_) => Err("api thread stopped unexpectedly".into()),
+            };
+        },
+        _ = shutdown.wait() => {},
+    }
+
+    // let the requests that are being handled right now finish
+    if let Ok(server) = server_rx.await {
+        server.stop(true).await;
+    }
+    let _ = done_rx.await;
+
+    info!("stopped api");
+    Ok(())
+}
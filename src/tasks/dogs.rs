@@ -0,0 +1,356 @@
+//! Crawls the top dogs of every live contest every minute, publishes them to the snapshot store
+//! and writes `top-dogs.json`, `top-dogs.csv` and `global-leaderboard.json` to the output
+//! directory, then a `dogs-manifest.json` listing what went out in that tick. The last crawl
+//! of a contest once it closes also goes to `closing/top-dogs-<page>.json`.
+
+use std::error::Error;
+
+use chrono::Utc;
+use futures::{stream, StreamExt};
+use tokio::time::{sleep_until, Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use log::{debug, error, info, warn};
+
+use crate::{
+    config::{self, parsed_setting},
+    events::{self, EventSettings},
+    export::Exporter,
+    fetch::{ContestTiming, CrawlLimits, Fetcher, TickMetrics},
+    history::History,
+    output::{Manifest, OutputDir},
+    schedule::Schedule,
+    scraper::{Coverage, CrawlDepth, GogoPhotoClient, SearchResults},
+    selectors::SelectorProfile,
+    snapshots::SnapshotStore,
+    stale::{ContestCrawl, LastGood},
+    tasks::Shutdown,
+    Contest, Contests, EntryData, EntryDataCSV, GLOBAL_LEADERBOARD_SIZE,
+};
+
+/// How often the deep crawl of every entry happens, it is off unless `every` is set
+#[derive(Debug, Clone, Copy)]
+pub struct DeepCrawl {
+    // every how many ticks, 0 to never deep crawl
+    pub every: u64,
+    pub max_pages: usize,
+}
+
+impl DeepCrawl {
+    /// Read from `--deep-crawl-every` and `--deep-crawl-max-pages`
+    /// (or `DEEP_CRAWL_EVERY` and `DEEP_CRAWL_MAX_PAGES`)
+    pub fn from_settings() -> DeepCrawl {
+        DeepCrawl {
+            every: parsed_setting("--deep-crawl-every", "DEEP_CRAWL_EVERY").unwrap_or(0),
+            max_pages: parsed_setting("--deep-crawl-max-pages", "DEEP_CRAWL_MAX_PAGES").unwrap_or(50).max(1),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.every > 0
+    }
+
+    /// The first tick is always a deep one so we know about every dog right away
+    pub fn depth(&self, tick: u64) -> CrawlDepth {
+        if self.enabled() && tick.is_multiple_of(self.every) {
+            CrawlDepth::Full { max_pages: self.max_pages }
+        } else {
+            CrawlDepth::Top
+        }
+    }
+}
+
+/// Every dog we know about. The deep crawls replace a contest wholesale and
+/// the top dog crawls in between keep the dogs they saw up to date.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AllDogs {
+    pub coverage: Vec<Coverage>,
+    pub entries: Vec<EntryData>,
+}
+
+impl AllDogs {
+    fn replace_contest(&mut self, coverage: Coverage, dogs: &[EntryData]) {
+        self.entries.retain(|dog| dog.contest.page != coverage.page);
+        self.entries.extend(dogs.iter().cloned());
+
+        self.coverage.retain(|c| c.page != coverage.page);
+        self.coverage.push(coverage);
+    }
+
+    fn refresh(&mut self, dogs: &[EntryData]) {
+        for dog in dogs {
+            match self.entries.iter_mut().find(|known| known.page == dog.page) {
+                Some(known) => *known = dog.clone(),
+                None => self.entries.push(dog.clone()),
+            }
+        }
+    }
+}
+
+/// Crawl the entries of a contest, only the top `num_dogs` unless `depth` says to go through every page
+pub async fn crawl_site(client: &GogoPhotoClient, contest: Contest, depth: CrawlDepth) -> Result<(Vec<EntryData>, Coverage), Box<dyn Error>> {
+    let SearchResults { entry_urls, coverage } = client.search_entries(&contest, depth).await?;
+
+    // crawl a few of the entry pages at a time, `buffered` keeps them in leaderboard order
+    let contest = &contest;
+    let dogs: Vec<EntryData> = stream::iter(entry_urls)
+        .map(|entry_page| async move {
+            let result = client.entry_detail(contest, &entry_page).await;
+            (entry_page, result)
+        })
+        .buffered(client.fetcher().limits().concurrency)
+        .filter_map(|(entry_page, result)| async move {
+            match result {
+                Ok(new_top_dog) => {
+                    debug!("successfully crawled entry page; entry_page={}", entry_page);
+                    Some(new_top_dog)
+                }
+                Err(e) => {
+                    warn!("something went wrong when trying to crawl the entry page; entry_page={}; error={}", entry_page, e);
+                    None
+                }
+            }
+        })
+        .collect()
+        .await;
+
+    info!("sucessfully got entries; c={}; n={}", contest.display_name, dogs.len());
+
+    Ok((dogs, coverage))
+}
+
+
+// crawl a few contests at a time, retrying and timing each of them
+async fn crawl_all(client: &GogoPhotoClient, contests: &[Contest], depth: CrawlDepth) -> Vec<(ContestCrawl<EntryData>, Option<Coverage>)> {
+    stream::iter(contests.iter().cloned())
+        .map(|contest| async move {
+            let started = Instant::now();
+            let page = contest.page.clone();
+            let (attempts, result) = client.fetcher().with_retries(&page, || crawl_site(client, contest.clone(), depth)).await;
+
+            let timing = ContestTiming {
+                page,
+                duration_ms: started.elapsed().as_millis() as u64,
+                results: result.as_ref().map_or(0, |(dogs, _)| dogs.len()),
+                attempts,
+                ok: result.is_ok(),
+            };
+
+            match result {
+                Ok((dogs, coverage)) => ((timing, Ok(dogs)), Some(coverage)),
+                Err(e) => ((timing, Err(e)), None),
+            }
+        })
+        .buffered(client.fetcher().limits().concurrency)
+        .collect()
+        .await
+}
+
+// lets do some web crawling!
+pub async fn run(contests: Contests, store: SnapshotStore, mut shutdown: Shutdown) -> Result<(), Box<dyn Error>> {
+    // the past seasons are done, they only live on in the history
+    let contests = contests.current();
+    info!("crawling season; season={:?}; contests={}", contests.current_season(), contests.get_all().len());
+
+    let mut history = match History::open_default() {
+        Ok(history) => history,
+        Err(e) => {
+            error!("Unable to open history database; error={}", e);
+            return Err(e.into());
+        }
+    };
+
+    let domain = config::domain();
+    let output = OutputDir::from_settings();
+    info!("writing files; output_dir={}", output.root().display());
+    let exporter = match Exporter::from_settings(&output) {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            error!("Unable to load export settings; error={}", e);
+            return Err(e.into());
+        }
+    };
+
+    let fetcher = Fetcher::new(CrawlLimits::from_settings(), Duration::from_secs(10))?;
+    info!("crawl limits; limits={:?}", fetcher.limits());
+    let selectors = match SelectorProfile::load() {
+        Ok(selectors) => selectors,
+        Err(e) => {
+            error!("Unable to load selector profile; error={}", e);
+            return Err(e.into());
+        }
+    };
+    let client = GogoPhotoClient::new(fetcher.clone(), &domain).with_selectors(selectors);
+
+    // pick up where the last run left off so a contest that fails right away still has something to show
+    let seed: Option<Vec<EntryData>> = match store.dogs() {
+        Some(dogs) => Some(dogs.to_vec()),
+        None => output.read_json("top-dogs.json").unwrap_or_default(),
+    };
+    let mut last_good = match &seed {
+        Some(dogs) => LastGood::seed(dogs.clone()),
+        None => LastGood::default(),
+    };
+
+    // the events are worked out against the last crawl, which is the last run's until we have one
+    let event_settings = EventSettings::from_settings();
+    let mut previous = seed.unwrap_or_default();
+    info!("events; top_n={}; vote_jump={}", event_settings.top_n, event_settings.vote_jump);
+
+    let deep_crawl = DeepCrawl::from_settings();
+    let mut all_dogs: AllDogs = match output.read_json("all-dogs.json") {
+        Ok(Some(all_dogs)) if deep_crawl.enabled() => all_dogs,
+        _ => AllDogs::default(),
+    };
+    info!("deep crawl; every={}; max_pages={}", deep_crawl.every, deep_crawl.max_pages);
+    let mut ticks: u64 = 0;
+
+    let schedule = Schedule::from_settings();
+    info!("schedule; idle_interval_secs={}", schedule.idle_interval.as_secs());
+
+    // Do this every minute, while there are contests to crawl
+    let mut next_tick = Instant::now();
+    loop {
+        tokio::select! {
+            _ = sleep_until(next_tick) => {},
+            _ = shutdown.wait() => break,
+        }
+        let tick_timestamp = Utc::now().timestamp();
+
+        let closing_crawls = history.closing_crawls("dogs").unwrap_or_else(|e| {
+            error!("Unable to read closing crawls from history; error={}", e);
+            Default::default()
+        });
+        let tick = schedule.plan(&contests.get_all(), &closing_crawls, tick_timestamp);
+        next_tick = Instant::now() + tick.wait;
+        if tick.idle() {
+            info!("idle, no contest is open; next_tick_secs={}", tick.wait.as_secs());
+
+            // the hour of the last crawls still has to go out once it is over
+            if let Err(e) = exporter.flush::<EntryDataCSV>(tick_timestamp) {
+                error!("Unable to export top dogs; error={}", e);
+            }
+            continue;
+        }
+
+        let depth = deep_crawl.depth(ticks);
+        ticks += 1;
+        info!("tick; depth={:?}; contests={}; closing={:?}", depth, tick.crawl.len(), tick.closing);
+        let mut manifest = Manifest::new("dogs", tick_timestamp);
+
+        // there is nothing to lose by dropping a crawl half way through,
+        // but once we have the results we always finish writing them
+        let started = Instant::now();
+        let (crawls, coverage): (Vec<_>, Vec<_>) = tokio::select! {
+            crawls = crawl_all(&client, &tick.crawl, depth) => crawls.into_iter().unzip(),
+            _ = shutdown.wait() => break,
+        };
+
+        let metrics = TickMetrics {
+            timestamp: tick_timestamp,
+            duration_ms: started.elapsed().as_millis() as u64,
+            limits: fetcher.limits(),
+            fetch: fetcher.take_stats(),
+            contests: crawls.iter().map(|(timing, _)| timing.clone()).collect(),
+        };
+        metrics.log("dogs");
+        manifest.add(output.write_json("dogs-metrics.json", &metrics)?);
+
+        if deep_crawl.enabled() {
+            for ((_, ret), coverage) in crawls.iter().zip(coverage) {
+                match (ret, coverage) {
+                    (Ok(dogs), Some(coverage)) if depth != CrawlDepth::Top => all_dogs.replace_contest(coverage, dogs),
+                    (Ok(dogs), _) => all_dogs.refresh(dogs),
+                    _ => {}
+                }
+            }
+
+            all_dogs.entries.sort_by_key(|entry| std::cmp::Reverse(entry.votes));
+            manifest.add(output.write_json("all-dogs.json", &all_dogs)?);
+            debug!("wrote json file; file=all-dogs.json; entries={}", all_dogs.entries.len());
+        }
+
+        // everything else only ever has the top dogs of each contest
+        let crawls = crawls
+            .into_iter()
+            .map(|(timing, ret)| {
+                let num_dogs = contests.from_page(&timing.page).map_or(usize::MAX, |contest| contest.num_dogs);
+                (timing, ret.map(|dogs| dogs.into_iter().take(num_dogs).collect()))
+            })
+            .collect();
+
+        // contests that failed fall back on their last good data
+        let (mut results, mut errors) = last_good.merge(tick_timestamp, crawls);
+        errors.record_health("dogs", client.take_health());
+        manifest.add(output.write_json("dogs-errors.json", &errors)?);
+
+        // if nothing came back there is nothing new to write
+        if errors.nothing_crawled() {
+            error!("Unable to crawl any contest; domain={}", domain);
+            continue;
+        }
+
+        // the contests that closed stay on the leaderboards as they ended
+        results.extend(last_good.closed(&tick.closed));
+
+        results.sort_by_key(|entry: &EntryData| std::cmp::Reverse(entry.votes));
+        let global_leaderboard: Vec<EntryData> = results.iter().take(GLOBAL_LEADERBOARD_SIZE).cloned().collect();
+
+        let events = events::detect(&previous, &results, event_settings, tick_timestamp);
+        if !events.is_empty() {
+            info!("noticed events; n={}", events.len());
+            if let Err(e) = history.record_events(&events) {
+                error!("Unable to save events to history; error={}", e);
+            }
+        }
+        previous = results.clone();
+
+        let version = store.publish_dogs(tick_timestamp, results.clone(), global_leaderboard.clone());
+        debug!("published dogs; version={}", version);
+
+        let rows: Vec<EntryDataCSV> = results.iter().map(EntryDataCSV::from_entry).collect();
+        manifest.add(output.write_csv("top-dogs.csv", rows.iter())?);
+        debug!("wrote csv file; file=top-dogs.csv");
+
+        // and the same rows for reporting, rolled up by the hour
+        if let Err(e) = exporter.export(tick_timestamp, &rows) {
+            error!("Unable to export top dogs; error={}", e);
+        }
+
+        // write the results to a json file
+        manifest.add(output.write_json_rows("top-dogs.json", &results)?);
+        debug!("wrote json file; file=top-dogs.json");
+
+        // keep every crawl around so we can look back at how the dogs did over time,
+        // stale and closed dogs are already in there from when they were crawled
+        let fresh: Vec<EntryData> = results.iter().filter(|dog| errors.crawled.contains(&dog.contest.page)).cloned().collect();
+        match history.record_entries(tick_timestamp, &fresh) {
+            Ok(crawl_id) => debug!("saved crawl to history; crawl_id={}", crawl_id),
+            Err(e) => error!("Unable to save crawl to history; error={}", e),
+        }
+
+        // write the results to the global leaderboard json file
+        manifest.add(output.write_json_rows("global-leaderboard.json", &global_leaderboard)?);
+        debug!("wrote json file; file=global-leaderboard.json");
+
+        // the contests that just closed are done being crawled once their last crawl made it,
+        // a closing crawl that failed gets another go next tick
+        for page in tick.closing.iter().filter(|page| errors.crawled.contains(page)) {
+            let dogs: Vec<EntryData> = results.iter().filter(|dog| &dog.contest.page == page).cloned().collect();
+            manifest.add(output.write_json_rows(&format!("closing/top-dogs-{}.json", page), &dogs)?);
+
+            match history.record_closing_crawl("dogs", page, tick_timestamp) {
+                Ok(()) => info!("took closing crawl; contest={}; dogs={}", page, dogs.len()),
+                Err(e) => error!("Unable to save closing crawl to history; contest={}; error={}", page, e),
+            }
+        }
+
+        // last so the manifest never points at files from a tick that hasn't finished writing
+        manifest.write(&output)?;
+
+        info!("done");
+    }
+
+    info!("stopped crawling dogs");
+    Ok(())
+}
@@ -0,0 +1,291 @@
+//! Crawls the fundraising totals of every live contest every minute, adds in the champ day
+//! money and a forecast of where each of them is headed, publishes them to the snapshot
+//! store and writes `contest-goals.json` and `contest-goals.csv` to the output directory,
+//! then a `goals-manifest.json` listing what went out in that tick. The last crawl of a
+//! contest once it closes (see `schedule`) also goes to `closing/contest-goals-<page>.json`.
+
+use std::error::Error;
+
+use chrono::Utc;
+use futures::{stream, StreamExt};
+
+use tokio::time::{sleep_until, Duration, Instant};
+
+use log::{debug, error, info, warn};
+
+use crate::{
+    config,
+    export::Exporter,
+    fetch::{ContestTiming, CrawlLimits, Fetcher, TickMetrics},
+    forecast::{self, ForecastSettings},
+    history::History,
+    output::{Manifest, OutputDir},
+    schedule::Schedule,
+    scraper::GogoPhotoClient,
+    selectors::SelectorProfile,
+    snapshots::SnapshotStore,
+    stale::{ContestCrawl, LastGood},
+    tasks::Shutdown,
+    CategoryReport, Contest, ContestData, ContestDataCSV, Contests, EntryData,
+};
+
+/// Crawl the fundraising totals of a contest
+pub async fn crawl_site(client: &GogoPhotoClient, contest: Contest) -> Result<ContestData, Box<dyn Error>> {
+    info!("getting contest summary; contest={}", contest.page);
+    let summary = client.contest_summary(&contest).await?;
+
+    let champ_day = contest.champ_day;
+    let now = Utc::now();
+
+    Ok(ContestData {
+        contest,
+        raised: summary.raised,
+        goal: summary.goal,
+        total_entries: summary.total_entries,
+        champ_day,
+        timestamp: now.timestamp(),
+        stale: false,
+        stale_age: 0,
+        forecast: None,
+    })
+}
+
+// crawl a few contests at a time, retrying and timing each of them
+async fn crawl_all(client: &GogoPhotoClient, contests: &[Contest]) -> Vec<ContestCrawl<ContestData>> {
+    stream::iter(contests.iter().cloned())
+        .map(|contest| async move {
+            let started = Instant::now();
+            let page = contest.page.clone();
+            let (attempts, result) = client.fetcher().with_retries(&page, || crawl_site(client, contest.clone())).await;
+
+            let timing = ContestTiming {
+                page,
+                duration_ms: started.elapsed().as_millis() as u64,
+                results: usize::from(result.is_ok()),
+                attempts,
+                ok: result.is_ok(),
+            };
+
+            (timing, result.map(|data| vec![data]))
+        })
+        .buffered(client.fetcher().limits().concurrency)
+        .collect()
+        .await
+}
+
+// lets do some web crawling!
+pub async fn run(contests: Contests, store: SnapshotStore, mut shutdown: Shutdown) -> Result<(), Box<dyn Error>> {
+    // the past seasons are done, they only live on in the history
+    let contests = contests.current();
+    info!("crawling season; season={:?}; contests={}", contests.current_season(), contests.get_all().len());
+
+    let mut history = match History::open_default() {
+        Ok(history) => history,
+        Err(e) => {
+            error!("Unable to open history database; error={}", e);
+            return Err(e.into());
+        }
+    };
+
+    let domain = config::domain();
+    let output = OutputDir::from_settings();
+    info!("writing files; output_dir={}", output.root().display());
+    let exporter = match Exporter::from_settings(&output) {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            error!("Unable to load export settings; error={}", e);
+            return Err(e.into());
+        }
+    };
+
+    let forecast_settings = ForecastSettings::from_settings();
+    info!("forecasting; window_hours={}", forecast_settings.window_hours);
+
+    let fetcher = Fetcher::new(CrawlLimits::from_settings(), Duration::from_secs(30))?;
+    info!("crawl limits; limits={:?}", fetcher.limits());
+    let selectors = match SelectorProfile::load() {
+        Ok(selectors) => selectors,
+        Err(e) => {
+            error!("Unable to load selector profile; error={}", e);
+            return Err(e.into());
+        }
+    };
+    let client = GogoPhotoClient::new(fetcher.clone(), &domain).with_selectors(selectors);
+
+    // pick up where the last run left off so a contest that fails right away still has something to show
+    let seed = match store.goals() {
+        Some(goals) => Some(goals.to_vec()),
+        None => output.read_json::<Vec<ContestData>>("contest-goals.json").unwrap_or_default(),
+    };
+    let mut last_good = match seed {
+        Some(mut goals) => {
+            // the champ day money gets added back in every tick
+            for goal in goals.iter_mut() {
+                goal.champ_day = goal.contest.champ_day;
+            }
+            LastGood::seed(goals)
+        }
+        None => LastGood::default(),
+    };
+
+    let schedule = Schedule::from_settings();
+    info!("schedule; idle_interval_secs={}", schedule.idle_interval.as_secs());
+
+    // Do this every minute, while there are contests to crawl
+    let mut next_tick = Instant::now();
+    loop {
+        tokio::select! {
+            _ = sleep_until(next_tick) => {},
+            _ = shutdown.wait() => break,
+        }
+        let tick_timestamp = Utc::now().timestamp();
+
+        let closing_crawls = history.closing_crawls("goals").unwrap_or_else(|e| {
+            error!("Unable to read closing crawls from history; error={}", e);
+            Default::default()
+        });
+        let tick = schedule.plan(&contests.get_all(), &closing_crawls, tick_timestamp);
+        next_tick = Instant::now() + tick.wait;
+        if tick.idle() {
+            info!("idle, no contest is open; next_tick_secs={}", tick.wait.as_secs());
+
+            // the hour of the last crawls still has to go out once it is over
+            if let Err(e) = exporter.flush::<ContestDataCSV>(tick_timestamp) {
+                error!("Unable to export contest goals; error={}", e);
+            }
+            continue;
+        }
+
+        info!("tick; contests={}; closing={:?}", tick.crawl.len(), tick.closing);
+        let mut manifest = Manifest::new("goals", tick_timestamp);
+
+        // there is nothing to lose by dropping a crawl half way through,
+        // but once we have the results we always finish writing them
+        let started = Instant::now();
+        let crawls = tokio::select! {
+            crawls = crawl_all(&client, &tick.crawl) => crawls,
+            _ = shutdown.wait() => break,
+        };
+
+        let metrics = TickMetrics {
+            timestamp: tick_timestamp,
+            duration_ms: started.elapsed().as_millis() as u64,
+            limits: fetcher.limits(),
+            fetch: fetcher.take_stats(),
+            contests: crawls.iter().map(|(timing, _)| timing.clone()).collect(),
+        };
+        metrics.log("goals");
+        manifest.add(output.write_json("goals-metrics.json", &metrics)?);
+
+        // contests that failed fall back on their last good data
+        let (mut results, mut errors) = last_good.merge(tick_timestamp, crawls);
+        errors.record_health("goals", client.take_health());
+        manifest.add(output.write_json("goals-errors.json", &errors)?);
+
+        // if nothing came back there is nothing new to write
+        if errors.nothing_crawled() {
+            error!("Unable to crawl any contest; domain={}", domain);
+            continue;
+        }
+
+        // the contests that closed keep showing how they ended
+        results.extend(last_good.closed(&tick.closed));
+
+        // champ day sync
+
+        // the top dogs come straight from the dogs crawler when it runs in this process,
+        // otherwise from the top dogs json file it wrote
+        let top_dogs = match store.dogs() {
+            Some(dogs) => Some(dogs.to_vec()),
+            None => output.read_json::<Vec<EntryData>>("top-dogs.json")?,
+        };
+
+        let mut category_report = CategoryReport::default();
+        if let Some(top_dogs) = top_dogs {
+
+            for dog in top_dogs.iter().filter(|dog| !dog.category.is_empty()) {
+                let found = contests.match_category(&dog.category);
+                category_report.record(&dog.category, found.as_ref());
+
+                if let Some(found) = found {
+                    let found_idx = results.iter().position(|c| c.contest.page == found.contest.page);
+
+                    if let Some(contest_idx) = found_idx {
+                        results[contest_idx].champ_day += dog.raised;
+                        info!("Added champ day amount to contest; amount={}; contest={}; match={:?}", &dog.raised, &found.contest.page, found.kind);
+                    } else {
+                        warn!("Unable to find contest for dog; dog={}; category={}", &dog.dog, &dog.category);
+                    }
+                } else {
+                    warn!("Unable to match dog with contest; dog={}; category={}", &dog.dog, &dog.category);
+                }
+            }
+        } else {
+            error!("Unable to find the top dogs, they are not in the snapshot store or the top dogs file");
+        }
+
+        if !category_report.unmatched.is_empty() {
+            warn!("Unmatched categories, add them as aliases in the contests file; categories={:?}", category_report.unmatched.keys().collect::<Vec<_>>());
+        }
+
+        manifest.add(output.write_json("category-report.json", &category_report)?);
+
+        // where each contest is headed, from the crawls in the history and this one
+        for goal in results.iter_mut() {
+            let from = tick_timestamp - forecast_settings.window_seconds();
+            let mut points = match history.contest_history(&goal.contest.page, from, tick_timestamp, None) {
+                Ok(records) => forecast::totals(&records),
+                Err(e) => {
+                    error!("Unable to read contest history for the forecast; contest={}; error={}", goal.contest.page, e);
+                    Vec::new()
+                }
+            };
+            // stale and closed contests are in there already from when they were crawled
+            if errors.crawled.contains(&goal.contest.page) {
+                points.push((goal.timestamp, goal.raised + goal.champ_day));
+            }
+            goal.forecast = Some(forecast::forecast(&points, goal.goal, goal.contest.ends_at, tick_timestamp));
+        }
+
+        let version = store.publish_goals(tick_timestamp, results.clone());
+        debug!("published goals; version={}", version);
+
+        let rows: Vec<ContestDataCSV> = results.iter().map(ContestDataCSV::from_contest_data).collect();
+        manifest.add(output.write_csv("contest-goals.csv", rows.iter())?);
+
+        // and the same rows for reporting, rolled up by the hour
+        if let Err(e) = exporter.export(tick_timestamp, &rows) {
+            error!("Unable to export contest goals; error={}", e);
+        }
+
+        // write the results to a json file
+        manifest.add(output.write_json_rows("contest-goals.json", &results)?);
+
+        // keep every crawl around so we can look back at how the contests did over time,
+        // stale and closed contests are already in there from when they were crawled
+        let fresh: Vec<ContestData> = results.iter().filter(|goal| errors.crawled.contains(&goal.contest.page)).cloned().collect();
+        match history.record_contests(tick_timestamp, &fresh) {
+            Ok(crawl_id) => debug!("saved crawl to history; crawl_id={}", crawl_id),
+            Err(e) => error!("Unable to save crawl to history; error={}", e),
+        }
+
+        // the contests that just closed are done being crawled once their last crawl made it,
+        // a closing crawl that failed gets another go next tick
+        for page in tick.closing.iter().filter(|page| errors.crawled.contains(page)) {
+            let goals: Vec<ContestData> = results.iter().filter(|goal| &goal.contest.page == page).cloned().collect();
+            manifest.add(output.write_json_rows(&format!("closing/contest-goals-{}.json", page), &goals)?);
+
+            match history.record_closing_crawl("goals", page, tick_timestamp) {
+                Ok(()) => info!("took closing crawl; contest={}", page),
+                Err(e) => error!("Unable to save closing crawl to history; contest={}; error={}", page, e),
+            }
+        }
+
+        // last so the manifest never points at files from a tick that hasn't finished writing
+        manifest.write(&output)?;
+        info!("done");
+    }
+
+    info!("stopped crawling contest goals");
+    Ok(())
+}
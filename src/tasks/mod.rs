@@ -0,0 +1,90 @@
+//! The long running loops that make up the crawler. They can each run in their own
+//! binary or all together in one process under the supervisor in `bin/crawler.rs`.
+
+use log::{error, info};
+use tokio::sync::watch;
+
+pub mod api;
+pub mod dogs;
+pub mod goals;
+pub mod notify;
+pub mod supervisor;
+pub mod upload;
+
+pub use supervisor::{supervise, Task, TaskState, TaskStatus, TaskStatuses};
+
+/// Tells the tasks when it is time to stop. The tasks check it between ticks
+/// so anything they are in the middle of writing still gets written.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    receiver: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    pub fn is_shutdown(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves once a shut down has been asked for
+    pub async fn wait(&mut self) {
+        while !self.is_shutdown() {
+            if self.receiver.changed().await.is_err() {
+                // nobody is left to tell us to shut down, so we never will
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+pub struct ShutdownTrigger {
+    sender: watch::Sender<bool>,
+}
+
+impl ShutdownTrigger {
+    pub fn trigger(&self) {
+        let _ = self.sender.send(true);
+    }
+}
+
+pub fn shutdown_channel() -> (ShutdownTrigger, Shutdown) {
+    let (sender, receiver) = watch::channel(false);
+
+    (ShutdownTrigger { sender }, Shutdown { receiver })
+}
+
+/// A shutdown that triggers on ctrl-c or SIGTERM
+pub fn shutdown_on_signals() -> Shutdown {
+    let (trigger, shutdown) = shutdown_channel();
+
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        info!("received signal, shutting down");
+        trigger.trigger();
+    });
+
+    shutdown
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(e) => {
+            error!("Unable to listen for SIGTERM; error={}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = terminate.recv() => {},
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
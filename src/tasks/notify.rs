@@ -0,0 +1,91 @@
+//! Sends out the milestones the crawls reach, see `crate::notify` for which ones and where to.
+//! Without any channels set up there is nothing to do, so it just waits to be shut down.
+
+use std::error::Error;
+
+use chrono::Utc;
+use tokio::time::{interval, Duration};
+
+use log::{error, info};
+
+use crate::{
+    history::History,
+    live::LiveState,
+    notify::{ChannelConfig, MilestoneSettings, Notifier},
+    output::OutputDir,
+    snapshots::SnapshotStore,
+    tasks::Shutdown,
+};
+
+pub async fn run(store: SnapshotStore, mut shutdown: Shutdown) -> Result<(), Box<dyn Error>> {
+    let configs = match ChannelConfig::from_settings() {
+        Ok(configs) => configs,
+        Err(e) => {
+            error!("Unable to load notification channels; error={}", e);
+            return Err(e.into());
+        }
+    };
+
+    if configs.is_empty() {
+        info!("no notification channels, not sending notifications");
+        shutdown.wait().await;
+        return Ok(());
+    }
+
+    let settings = match MilestoneSettings::from_settings() {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!("Unable to load notification milestones; error={}", e);
+            return Err(e.into());
+        }
+    };
+    let channels = configs.into_iter().map(ChannelConfig::build).collect::<Result<Vec<_>, _>>()?;
+    let notifier = Notifier::new(channels, settings.clone());
+    let described: Vec<String> = notifier.channels().iter().map(|channel| channel.describe()).collect();
+    info!("sending notifications; channels={:?}; milestones={:?}", described, settings);
+
+    let mut history = match History::open_default() {
+        Ok(history) => history,
+        Err(e) => {
+            error!("Unable to open history database; error={}", e);
+            return Err(e.into());
+        }
+    };
+    let output = OutputDir::from_settings();
+
+    // the last store version we looked at
+    let mut checked = 0;
+    let mut interval = interval(Duration::from_secs(15));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {},
+            _ = shutdown.wait() => break,
+        }
+
+        // the crawls come from the store when the crawlers run in this process, otherwise from their files
+        let version = store.version();
+        let state = if version == 0 {
+            LiveState::read(&output)
+        } else if version != checked {
+            checked = version;
+            store.live_state()
+        } else {
+            None
+        };
+
+        let state = match state {
+            Some(state) => state,
+            None => continue,
+        };
+
+        // not dropped half way on shut down, a milestone could get remembered without going out
+        match notifier.notify(&mut history, &state, Utc::now().timestamp()).await {
+            Ok(sent) if !sent.is_empty() => info!("done; sent={}", sent.len()),
+            Ok(_) => {}
+            Err(e) => error!("Unable to send notifications; error={}", e),
+        }
+    }
+
+    info!("stopped sending notifications");
+    Ok(())
+}
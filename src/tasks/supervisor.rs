@@ -0,0 +1,178 @@
+//! Runs the tasks, restarting them with a backoff when they fail
+//! and keeping track of how each of them is doing.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use futures::future::LocalBoxFuture;
+use log::{error, info, warn};
+use serde::Serialize;
+
+use crate::{
+    output::OutputDir,
+    snapshots::SnapshotStore,
+    tasks::{api, dogs, goals, notify, upload, Shutdown},
+    Contests,
+};
+
+// where the status of every task gets written in the output directory whenever it changes
+pub const TASK_STATUS_FILE: &str = "task-status.json";
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+// a task that ran this long before failing starts over with the initial backoff
+const BACKOFF_RESET_AFTER: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Task {
+    Dogs,
+    Goals,
+    Upload,
+    Api,
+    Notify,
+}
+
+impl Task {
+    pub const ALL: [Task; 5] = [Task::Dogs, Task::Goals, Task::Upload, Task::Api, Task::Notify];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Task::Dogs => "dogs",
+            Task::Goals => "goals",
+            Task::Upload => "upload",
+            Task::Api => "api",
+            Task::Notify => "notify",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Task> {
+        Task::ALL.iter().copied().find(|task| task.name() == name)
+    }
+
+    pub fn run(self, contests: Contests, store: SnapshotStore, shutdown: Shutdown) -> LocalBoxFuture<'static, Result<(), Box<dyn Error>>> {
+        match self {
+            Task::Dogs => Box::pin(dogs::run(contests, store, shutdown)),
+            Task::Goals => Box::pin(goals::run(contests, store, shutdown)),
+            Task::Upload => Box::pin(upload::run(store, shutdown)),
+            Task::Api => Box::pin(api::run(contests, store, shutdown)),
+            Task::Notify => Box::pin(notify::run(store, shutdown)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    // the task failed and is waiting for its backoff to run out
+    Restarting,
+    Stopped,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TaskStatus {
+    pub state: TaskState,
+    pub restarts: usize,
+    pub last_error: Option<String>,
+    // when the task got into its current state
+    pub since: i64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TaskStatuses {
+    inner: Arc<Mutex<BTreeMap<&'static str, TaskStatus>>>,
+}
+
+impl TaskStatuses {
+    pub fn get(&self) -> BTreeMap<&'static str, TaskStatus> {
+        self.inner.lock().unwrap().clone()
+    }
+
+    fn set(&self, task: Task, state: TaskState, error: Option<String>) {
+        let statuses = {
+            let mut statuses = self.inner.lock().unwrap();
+            let status = statuses.entry(task.name()).or_insert(TaskStatus {
+                state,
+                restarts: 0,
+                last_error: None,
+                since: 0,
+            });
+
+            if state == TaskState::Restarting {
+                status.restarts += 1;
+            }
+            if error.is_some() {
+                status.last_error = error;
+            }
+            status.state = state;
+            status.since = Utc::now().timestamp();
+
+            statuses.clone()
+        };
+
+        let summary = statuses
+            .iter()
+            .map(|(name, status)| format!("{}={:?}", name, status.state))
+            .collect::<Vec<_>>()
+            .join(" ");
+        info!("task status; {}", summary);
+
+        match serde_json::to_string(&statuses) {
+            Ok(serialized) => {
+                if let Err(e) = OutputDir::from_settings().write(TASK_STATUS_FILE, serialized.as_bytes()) {
+                    warn!("Unable to write task status; file={}; error={}", TASK_STATUS_FILE, e);
+                }
+            }
+            Err(e) => warn!("Unable to serialize task status; error={}", e),
+        }
+    }
+}
+
+/// Keep running `task` until we are told to shut down, restarting it with an exponential
+/// backoff whenever it fails or panics. Has to be run inside a `tokio::task::LocalSet`.
+/// Every task sharing `store` sees the others' crawls as soon as they are published.
+pub async fn supervise(task: Task, contests: Contests, store: SnapshotStore, statuses: TaskStatuses, mut shutdown: Shutdown) {
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        statuses.set(task, TaskState::Running, None);
+        let started = Instant::now();
+
+        // running it as its own task means a panic only takes down this task
+        let result = tokio::task::spawn_local(task.run(contests.clone(), store.clone(), shutdown.clone())).await;
+
+        if shutdown.is_shutdown() {
+            statuses.set(task, TaskState::Stopped, None);
+            info!("task stopped; task={}", task.name());
+            return;
+        }
+
+        let error = match result {
+            Ok(Ok(())) => "task stopped on its own".to_string(),
+            Ok(Err(e)) => e.to_string(),
+            Err(e) => format!("task panicked: {}", e),
+        };
+
+        if started.elapsed() >= BACKOFF_RESET_AFTER {
+            backoff = INITIAL_BACKOFF;
+        }
+
+        error!("Task failed, restarting it; task={}; backoff={:?}; error={}", task.name(), backoff, error);
+        statuses.set(task, TaskState::Restarting, Some(error));
+
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {},
+            _ = shutdown.wait() => {
+                statuses.set(task, TaskState::Stopped, None);
+                return;
+            }
+        }
+
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,301 @@
+//! Upload the generated csv files to google cloud storage (or whichever sink
+//! is set up) so we can then do some reporting on them in datastudio
+//!
+//! Every minute the latest csv files get queued in the spool and then the spool is
+//! uploaded oldest first. A file only leaves the spool once the sink has taken it, when
+//! an upload fails the whole queue waits with an exponential backoff and carries on
+//! from where it stopped. `crawler replay-uploads` pushes the spool right away.
+//! Snapshots with the same data as the last one don't get queued at all, see `Dedupe`.
+
+use std::{collections::HashMap, error::Error, time::Duration};
+
+use chrono::Utc;
+use tokio::time::{interval, Instant};
+
+use log::{info, error, warn};
+
+use crate::{
+    output::{csv_bytes, OutputDir},
+    sinks::{Sink, SinkConfig},
+    snapshots::SnapshotStore,
+    spool::{mime_type, Dedupe, Spool, Upload},
+    tasks::Shutdown,
+    ContestDataCSV, EntryDataCSV,
+};
+
+// the csv files the crawlers write, without the .csv
+const UPLOADED_FILES: [&str; 2] = ["top-dogs", "contest-goals"];
+
+// how long the queue waits after the first failed upload, it doubles with every failure after that
+const INITIAL_BACKOFF: Duration = Duration::from_secs(60);
+const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+// what the files are called while we are moving them from the output directory to the spool
+const CLAIMED_SUFFIX: &str = ".uploading";
+
+pub async fn run(store: SnapshotStore, mut shutdown: Shutdown) -> Result<(), Box<dyn Error>> {
+    let sink = load_sink()?;
+    let output = OutputDir::from_settings();
+    let spool = Spool::from_settings(&output);
+    info!("uploading files; sink={}; spool={}", sink.describe(), spool.dir().display());
+
+    if !output.enabled() {
+        info!("writing files is turned off, uploading the crawls from the snapshot store");
+    }
+    // the store version of the last crawl of each file we queued
+    let mut queued: HashMap<&str, u64> = HashMap::new();
+    let mut dedupe = Dedupe::from_settings();
+    let mut backoff = Backoff::default();
+
+    let mut interval = interval(Duration::from_secs(60));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {},
+            _ = shutdown.wait() => break,
+        }
+        info!("tick");
+        for name in UPLOADED_FILES.iter() {
+            let upload_filename = format!("{}-{}.csv", name, Utc::now().timestamp());
+
+            if output.enabled() {
+                claim_file(&output, name, &upload_filename);
+            } else {
+                queue_from_store(&store, &spool, &mut dedupe, name, &upload_filename, &mut queued);
+            }
+        }
+        // this also picks up anything a restart left behind half way to the spool
+        spool_claimed(&output, &spool, &mut dedupe);
+
+        if let Some(retry_in) = backoff.remaining() {
+            info!("holding off uploads; retry_in={:?}", retry_in);
+            continue;
+        }
+
+        // a file that was being uploaded when we got told to stop is still in the spool
+        let drained = tokio::select! {
+            drained = drain(sink.as_ref(), &spool) => drained,
+            _ = shutdown.wait() => break,
+        };
+
+        match &drained.failed {
+            None => backoff.reset(),
+            Some((file, e)) => {
+                let retry_in = backoff.failed();
+                error!("Unable to upload file, holding off uploads; file={}; retry_in={:?}; pending={}; error={}", file, retry_in, drained.pending, e);
+            }
+        }
+
+        info!("done; uploaded={}; pending={}", drained.uploaded, drained.pending);
+    }
+
+    info!("stopped uploading files");
+    Ok(())
+}
+
+/// Push everything waiting in the spool right away, for after an outage
+pub async fn replay() -> Result<Drained, Box<dyn Error>> {
+    let sink = load_sink()?;
+    let output = OutputDir::from_settings();
+    let spool = Spool::from_settings(&output);
+    info!("replaying uploads; sink={}; spool={}", sink.describe(), spool.dir().display());
+
+    // nothing to compare the left behind files with, so they all go up
+    spool_claimed(&output, &spool, &mut Dedupe::default());
+    Ok(drain(sink.as_ref(), &spool).await)
+}
+
+fn load_sink() -> Result<Box<dyn Sink>, Box<dyn Error>> {
+    match SinkConfig::from_settings() {
+        Ok(config) => config.build(),
+        Err(e) => {
+            error!("Unable to load upload sink; error={}", e);
+            Err(e.into())
+        }
+    }
+}
+
+/// How a run through the spool went
+#[derive(Debug, Default)]
+pub struct Drained {
+    pub uploaded: usize,
+    // how many files are still waiting
+    pub pending: usize,
+    // the file that didn't go up and why, nothing after it was tried
+    pub failed: Option<(String, String)>,
+}
+
+/// Upload everything in the spool, oldest first. It stops at the first failure since the sink
+/// is most likely down and the rest would only fail too, this way they also keep their order.
+pub async fn drain(sink: &dyn Sink, spool: &Spool) -> Drained {
+    let names = match spool.pending() {
+        Ok(names) => names,
+        Err(e) => {
+            error!("Unable to read spool; dir={}; error={}", spool.dir().display(), e);
+            return Drained {
+                failed: Some((spool.dir().display().to_string(), e.to_string())),
+                ..Drained::default()
+            };
+        }
+    };
+
+    let mut drained = Drained {
+        pending: names.len(),
+        ..Drained::default()
+    };
+
+    for name in names {
+        let buf = match spool.read(&name) {
+            Ok(buf) => buf,
+            Err(e) => {
+                drained.failed = Some((name, e.to_string()));
+                break;
+            }
+        };
+
+        // We are getting connection error thats originate from google cloud itself so we have to
+        // make this able to handle those errors and just try again when it can instead of just
+        // panicing
+        if let Err(e) = sink.put(&name, buf, mime_type(&name)).await {
+            drained.failed = Some((name, e.to_string()));
+            break;
+        }
+
+        info!("Upload successful; file={}", name);
+        drained.uploaded += 1;
+        drained.pending -= 1;
+
+        // if this fails the file just gets uploaded again over the top of itself
+        if let Err(e) = spool.remove(&name) {
+            error!("Unable to remove file from spool; file={}; error={}", name, e);
+        }
+    }
+
+    drained
+}
+
+// Holds off the whole queue after a failed upload
+#[derive(Debug, Default)]
+struct Backoff {
+    delay: Option<Duration>,
+    until: Option<Instant>,
+}
+
+impl Backoff {
+    // how much longer to wait before trying again, if we are waiting at all
+    fn remaining(&self) -> Option<Duration> {
+        self.until
+            .map(|until| until.saturating_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    fn failed(&mut self) -> Duration {
+        let delay = match self.delay {
+            Some(delay) => (delay * 2).min(MAX_BACKOFF),
+            None => INITIAL_BACKOFF,
+        };
+
+        self.delay = Some(delay);
+        self.until = Some(Instant::now() + delay);
+        delay
+    }
+
+    fn reset(&mut self) {
+        *self = Backoff::default();
+    }
+}
+
+// Take the file away from the crawler first, otherwise it could write the next one
+// while we are working on this one and we would delete that without ever uploading it
+fn claim_file(output: &OutputDir, name: &str, upload_filename: &str) {
+    let file = format!("{}.csv", name);
+
+    match output.claim(&file, &format!(".{}{}", upload_filename, CLAIMED_SUFFIX)) {
+        Ok(Some(_)) => {}
+        Ok(None) => info!("Nothing new to upload; file={}", file),
+        Err(e) => error!("Unable to claim file; file={}; error={}", file, e),
+    }
+}
+
+// Put a snapshot in the spool, unless it has the same data as the last one
+fn queue(spool: &Spool, dedupe: &mut Dedupe, upload_filename: &str, buf: &[u8]) -> std::io::Result<()> {
+    let upload = dedupe.check(upload_filename, buf, Utc::now().timestamp());
+    if upload == Upload::Unchanged {
+        info!("Skipping unchanged snapshot; file={}", upload_filename);
+        return Ok(());
+    }
+
+    if let Err(e) = spool.enqueue(upload_filename, buf) {
+        dedupe.forget(upload_filename);
+        return Err(e);
+    }
+
+    info!("Queued file; file={}; upload={:?}", upload_filename, upload);
+    Ok(())
+}
+
+// Move every claimed file in to the spool, a claimed file is only removed once it is in there
+fn spool_claimed(output: &OutputDir, spool: &Spool, dedupe: &mut Dedupe) {
+    let entries = match std::fs::read_dir(output.root()) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let claimed = entry.file_name().to_string_lossy().to_string();
+        let upload_filename = match claimed.strip_prefix('.').and_then(|name| name.strip_suffix(CLAIMED_SUFFIX)) {
+            Some(upload_filename) => upload_filename,
+            None => continue,
+        };
+
+        let queued = std::fs::read(entry.path()).and_then(|buf| queue(spool, dedupe, upload_filename, &buf));
+        match queued {
+            Ok(()) => {
+                if let Err(e) = std::fs::remove_file(entry.path()) {
+                    warn!("Unable to remove file; file={}; error={}", entry.path().display(), e);
+                }
+            }
+            Err(e) => error!("Unable to queue file; file={}; error={}", upload_filename, e),
+        }
+    }
+}
+
+// With the files turned off the csv is made from the snapshot store instead,
+// as long as there has been a crawl since the last one we queued
+fn queue_from_store<'a>(
+    store: &SnapshotStore,
+    spool: &Spool,
+    dedupe: &mut Dedupe,
+    name: &'a str,
+    upload_filename: &str,
+    queued: &mut HashMap<&'a str, u64>,
+) {
+    let last = queued.get(name).copied().unwrap_or(0);
+
+    let csv = match name {
+        "top-dogs" => store
+            .dogs()
+            .filter(|dogs| dogs.version > last)
+            .map(|dogs| (dogs.version, csv_bytes(dogs.data.iter().map(EntryDataCSV::from_entry)))),
+        "contest-goals" => store
+            .goals()
+            .filter(|goals| goals.version > last)
+            .map(|goals| (goals.version, csv_bytes(goals.data.iter().map(ContestDataCSV::from_contest_data)))),
+        _ => None,
+    };
+
+    let (version, csv) = match csv {
+        Some(csv) => csv,
+        None => {
+            info!("Nothing new to upload; file={}.csv", name);
+            return;
+        }
+    };
+
+    let queued_file = csv.and_then(|(buf, _)| Ok(queue(spool, dedupe, upload_filename, &buf)?));
+    match queued_file {
+        Ok(()) => {
+            queued.insert(name, version);
+        }
+        Err(e) => error!("Unable to queue file; file={}; error={}", upload_filename, e),
+    }
+}
@@ -0,0 +1,81 @@
+use oshkosh_kiwanis_web_crawler::{CategoryReport, Contests, MatchKind};
+
+fn contests() -> Contests {
+    Contests::from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/contests.toml")).unwrap()
+}
+
+// The "Entry Category:" strings the old substring matcher was written against,
+// plus the variations of them that show up on the entry pages. Add any new
+// category from `category-report.json` here along with its alias.
+const SEEN_CATEGORIES: &[(&str, &str)] = &[
+    ("Lakeshore Humane Society", "newtopdoglakeshorefall2022"),
+    ("Lakeshore", "newtopdoglakeshorefall2022"),
+    ("lakeshore humane society champ day", "newtopdoglakeshorefall2022"),
+    ("Misfit Mutts", "newtopdogmisfitfall2022"),
+    ("Misfit Mutt", "newtopdogmisfitfall2022"),
+    ("Misfit Mutts Rescue", "newtopdogmisfitfall2022"),
+    ("Neenah", "newtopdogneenahfall2022"),
+    ("Neenah Animal Shelter", "newtopdogneenahfall2022"),
+    ("Mit Liebe", "newtopdogmitliebefall2022"),
+    ("Mit Liebe Rescue", "newtopdogmitliebefall2022"),
+    ("Oshkosh", "newtopdogoahsfall2022"),
+    ("Oshkosh Area Humane Society", "newtopdogoahsfall2022"),
+    ("Oshskosh Area Humane Society", "newtopdogoahsfall2022"),
+    ("OAHS", "newtopdogoahsfall2022"),
+    ("Sandi Paws", "newtopdogsandipawsfall2022"),
+    ("Sandi Paw", "newtopdogsandipawsfall2022"),
+    ("Sandi Paws's Champ Day", "newtopdogsandipawsfall2022"),
+];
+
+#[test]
+fn every_seen_category_matches_its_contest() {
+    let contests = contests();
+
+    for (category, page) in SEEN_CATEGORIES {
+        let found = contests.match_category(category);
+        assert_eq!(found.map(|f| f.contest.page), Some(page.to_string()), "category={:?}", category);
+    }
+}
+
+#[test]
+fn match_kinds_go_from_strict_to_loose() {
+    let contests = contests();
+
+    let kind = |category: &str| contests.match_category(category).unwrap().kind;
+
+    assert_eq!(kind("Mit Liebe"), MatchKind::Exact);
+    assert_eq!(kind("MIT LIEBE"), MatchKind::CaseInsensitive);
+    assert_eq!(kind("newtopdogneenahfall2022"), MatchKind::Exact);
+    assert_eq!(kind("Sandi Paws's Champ Day"), MatchKind::Normalized);
+    assert_eq!(kind("Oshskosh"), MatchKind::Fuzzy);
+}
+
+#[test]
+fn longest_alias_wins() {
+    let found = contests().match_category("Oshkosh Area Humane Society").unwrap();
+
+    assert_eq!(found.alias, "Oshkosh Area Humane Society");
+}
+
+#[test]
+fn unknown_categories_do_not_match() {
+    let contests = contests();
+
+    for category in &["", "   ", "General", "Best Cat", "Humane Society", "Mit"] {
+        assert!(contests.match_category(category).is_none(), "category={:?}", category);
+    }
+}
+
+#[test]
+fn report_counts_unmatched_categories() {
+    let contests = contests();
+    let mut report = CategoryReport::default();
+
+    for category in &["Neenah", "Best Cat", "Best Cat", "General"] {
+        report.record(category, contests.match_category(category).as_ref());
+    }
+
+    assert_eq!(report.matched.len(), 1);
+    assert_eq!(report.unmatched.get("Best Cat"), Some(&2));
+    assert_eq!(report.unmatched.get("General"), Some(&1));
+}
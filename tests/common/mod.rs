@@ -0,0 +1,252 @@
+//! A tiny stand in for gogophotocontest.com that serves the saved pages in `tests/fixtures`,
+//! or for anything else that speaks plain http, one for an smtp server, and the contests and
+//! dogs the tests crawl
+
+// not every test uses all of it
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use oshkosh_kiwanis_web_crawler::{Contest, EntryData, Money};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+// the contest the fixtures were saved from
+pub const NEENAH: &str = "newtopdogneenahfall2022";
+
+pub fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/gogophoto/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("unable to read fixture {}: {}", path, e))
+}
+
+/// A contest on `page`, named after it
+pub fn contest(page: &str) -> Contest {
+    Contest {
+        display_name: format!("{} contest", page),
+        page: page.into(),
+        num_dogs: 15,
+        ..Contest::default()
+    }
+}
+
+/// A dog in `contest` with a dollar raised for every vote, its entry id is its name in lowercase
+pub fn entry(contest: &Contest, dog: &str, votes: usize) -> EntryData {
+    EntryData {
+        dog: dog.into(),
+        votes,
+        raised: Money::from_dollars(votes as i64),
+        contest: contest.clone(),
+        category: "".into(),
+        page: format!("https://www.gogophotocontest.com/{}/entries/{}", contest.page, dog.to_lowercase()),
+        picture: "".into(),
+        timestamp: 0,
+        stale: false,
+        stale_age: 0,
+    }
+}
+
+/// A request the mock site got
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    // with lowercase names
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct MockSite {
+    pub domain: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockSite {
+    /// Serve `routes`, a map of `"METHOD /path"` to the html to send back.
+    /// Anything else gets a 404.
+    pub async fn start(routes: Vec<(&str, String)>) -> MockSite {
+        let routes: Arc<HashMap<String, String>> = Arc::new(routes.into_iter().map(|(route, body)| (route.to_string(), body)).collect());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let domain = format!("http://{}", listener.local_addr().unwrap());
+
+        let served = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle(stream, routes.clone(), served.clone()));
+            }
+        });
+
+        MockSite { domain, requests }
+    }
+
+    /// The site serving the neenah contest from the fixtures
+    pub async fn neenah() -> MockSite {
+        let mut routes = vec![
+            ("GET /newtopdogneenahfall2022", fixture("contest.html")),
+            ("GET /newtopdogneenahfall2022/search", fixture("search.html")),
+            ("POST /newtopdogneenahfall2022/search", fixture("search-page-2.html")),
+        ];
+        for (route, entry) in &[
+            ("GET /newtopdogneenahfall2022/entries/101", "entry-101.html"),
+            ("GET /newtopdogneenahfall2022/entries/102", "entry-102.html"),
+            ("GET /newtopdogneenahfall2022/entries/103", "entry-103.html"),
+            ("GET /newtopdogneenahfall2022/entries/104", "entry-104.html"),
+        ] {
+            routes.push((route, fixture(entry)));
+        }
+
+        MockSite::start(routes).await
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn handle(mut stream: TcpStream, routes: Arc<HashMap<String, String>>, requests: Arc<Mutex<Vec<Request>>>) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    // read the headers, then however much body they say there is
+    let header_end = loop {
+        let n = match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(end) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let content_length = head
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while buf.len() < header_end + content_length {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+
+    let mut request_line = head.lines().next().unwrap_or("").split_whitespace();
+    let request = Request {
+        method: request_line.next().unwrap_or("").to_string(),
+        path: request_line.next().unwrap_or("").to_string(),
+        headers: head
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect(),
+        body: String::from_utf8_lossy(&buf[header_end..]).to_string(),
+    };
+
+    let (status, body) = match routes.get(&format!("{} {}", request.method, request.path)) {
+        Some(body) => ("200 OK", body.clone()),
+        None => ("404 Not Found", "<html><body><h1>Page not found</h1></body></html>".to_string()),
+    };
+    requests.lock().unwrap().push(request);
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// An email the mock smtp server took
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub from: String,
+    pub to: Vec<String>,
+    pub data: String,
+}
+
+/// Takes every email it is sent without any auth or tls
+pub struct MockSmtp {
+    pub port: u16,
+    emails: Arc<Mutex<Vec<Email>>>,
+}
+
+impl MockSmtp {
+    pub async fn start() -> MockSmtp {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let emails = Arc::new(Mutex::new(Vec::new()));
+
+        let received = emails.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(smtp_session(stream, received.clone()));
+            }
+        });
+
+        MockSmtp { port, emails }
+    }
+
+    pub fn emails(&self) -> Vec<Email> {
+        self.emails.lock().unwrap().clone()
+    }
+}
+
+async fn smtp_session(stream: TcpStream, emails: Arc<Mutex<Vec<Email>>>) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    let mut email = Email { from: String::new(), to: vec![], data: String::new() };
+
+    let _ = write.write_all(b"220 localhost mock smtp\r\n").await;
+    while let Ok(Some(line)) = lines.next_line().await {
+        let command = line.to_uppercase();
+        let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+            b"250-localhost\r\n250 8BITMIME\r\n"
+        } else if command.starts_with("MAIL FROM:") {
+            email.from = line[10..].trim().trim_matches(|c| c == '<' || c == '>').to_string();
+            b"250 ok\r\n"
+        } else if command.starts_with("RCPT TO:") {
+            email.to.push(line[8..].trim().trim_matches(|c| c == '<' || c == '>').to_string());
+            b"250 ok\r\n"
+        } else if command == "DATA" {
+            let _ = write.write_all(b"354 go ahead\r\n").await;
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line == "." {
+                    break;
+                }
+                email.data.push_str(&line);
+                email.data.push('\n');
+            }
+            emails.lock().unwrap().push(std::mem::replace(&mut email, Email { from: String::new(), to: vec![], data: String::new() }));
+            b"250 queued\r\n"
+        } else if command == "QUIT" {
+            let _ = write.write_all(b"221 bye\r\n").await;
+            return;
+        } else {
+            b"250 ok\r\n"
+        };
+
+        if write.write_all(reply).await.is_err() {
+            return;
+        }
+    }
+}
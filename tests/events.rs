@@ -0,0 +1,71 @@
+mod common;
+
+use common::{contest, entry};
+use oshkosh_kiwanis_web_crawler::{
+    events::{detect, Event, EventKind, EventSettings},
+    EntryData,
+};
+
+// sorted by votes like the crawler does
+fn crawl(dogs: &[(&str, &str, usize)]) -> Vec<EntryData> {
+    let mut dogs: Vec<EntryData> = dogs.iter().map(|(page, dog, votes)| entry(&contest(page), dog, *votes)).collect();
+    dogs.sort_by_key(|dog| std::cmp::Reverse(dog.votes));
+    dogs
+}
+
+// the kind, the dog, its ranks before and after, and the other dog
+type Summary<'a> = (EventKind, &'a str, Option<usize>, Option<usize>, Option<&'a str>);
+
+fn summary(events: &[Event]) -> Vec<Summary<'_>> {
+    events
+        .iter()
+        .map(|e| (e.kind, e.dog.as_str(), e.previous_rank, e.rank, e.other_dog.as_deref()))
+        .collect()
+}
+
+const SETTINGS: EventSettings = EventSettings { top_n: 2, vote_jump: 50 };
+
+#[test]
+fn nothing_moving_is_nothing_happening() {
+    let dogs = crawl(&[("neenah", "rex", 10), ("neenah", "fido", 5), ("oshkosh", "spot", 7)]);
+
+    assert!(detect(&dogs, &dogs, SETTINGS, 60).is_empty());
+    // a contest we have never seen before has nothing to compare with
+    assert!(detect(&[], &dogs, SETTINGS, 60).is_empty());
+}
+
+#[test]
+fn overtaking_the_leader() {
+    let previous = crawl(&[("neenah", "rex", 10), ("neenah", "fido", 5), ("neenah", "spot", 1), ("oshkosh", "max", 3)]);
+    let current = crawl(&[("neenah", "rex", 10), ("neenah", "fido", 11), ("neenah", "spot", 80), ("oshkosh", "max", 4)]);
+
+    let events = detect(&previous, &current, SETTINGS, 60);
+    assert_eq!(
+        summary(&events),
+        vec![
+            (EventKind::NewLeader, "spot", Some(3), Some(1), Some("rex")),
+            (EventKind::EnteredTop, "spot", Some(3), Some(1), None),
+            (EventKind::Overtake, "spot", Some(3), Some(1), Some("rex")),
+            (EventKind::Overtake, "spot", Some(3), Some(1), Some("fido")),
+            (EventKind::VoteJump, "spot", Some(3), Some(1), None),
+            (EventKind::Overtake, "fido", Some(2), Some(2), Some("rex")),
+            (EventKind::LeftTop, "rex", Some(1), Some(3), None),
+        ]
+    );
+    assert_eq!((events[4].previous_votes, events[4].votes), (Some(1), 80));
+    assert!(events.iter().all(|e| e.contest == "neenah" && e.timestamp == 60));
+}
+
+#[test]
+fn dropping_out_of_the_crawl_leaves_the_top() {
+    let previous = crawl(&[("neenah", "rex", 10), ("neenah", "fido", 5)]);
+    let current = crawl(&[("neenah", "rex", 12), ("neenah", "spot", 6)]);
+
+    assert_eq!(
+        summary(&detect(&previous, &current, SETTINGS, 60)),
+        vec![
+            (EventKind::EnteredTop, "spot", None, Some(2), None),
+            (EventKind::LeftTop, "fido", Some(2), None, None),
+        ]
+    );
+}
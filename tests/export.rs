@@ -0,0 +1,191 @@
+mod common;
+
+use std::{
+    collections::HashSet,
+    io::{BufRead, BufReader},
+};
+
+use chrono::{TimeZone, Utc};
+use common::contest;
+use flate2::read::GzDecoder;
+use oshkosh_kiwanis_web_crawler::{
+    export::{ExportFormat, Exporter},
+    money::Money,
+    schedule::Schedule,
+    spool::Spool,
+    Contest, ContestDataCSV,
+};
+use parquet::{
+    file::reader::{FileReader, SerializedFileReader},
+    record::{Field, RowAccessor},
+};
+use tempfile::TempDir;
+
+fn goal(page: &str, raised_cents: i64, timestamp: i64) -> ContestDataCSV {
+    ContestDataCSV {
+        display_name: format!("{} contest", page),
+        page: page.to_string(),
+        goal: Money::from_dollars(10_000),
+        raised: Money::from_cents(raised_cents),
+        total_entries: 42,
+        champ_day: Money::from_cents(0),
+        timestamp,
+        ends_at: None,
+        velocity_per_hour: None,
+        projected_total: None,
+        required_per_hour: None,
+    }
+}
+
+// with the columns that can be empty filled in
+fn forecast(goal: ContestDataCSV) -> ContestDataCSV {
+    ContestDataCSV {
+        ends_at: Some(at(20, 0)),
+        velocity_per_hour: Some(Money::from_dollars(25)),
+        projected_total: Some(Money::from_cents(117_551)),
+        ..goal
+    }
+}
+
+fn at(hour: u32, minute: u32) -> i64 {
+    Utc.with_ymd_and_hms(2022, 10, 1, hour, minute, 0).unwrap().timestamp()
+}
+
+#[test]
+fn hours_only_roll_once_they_are_over() {
+    let temp = TempDir::new().unwrap();
+    let dir = temp.path();
+    let exporter = Exporter::new(dir, vec![ExportFormat::Ndjson, ExportFormat::Parquet]);
+
+    for minute in 0..3 {
+        let tick = at(13, minute);
+        let rolled = exporter
+            .export(tick, &[forecast(goal("neenah", 100_050 + minute as i64, tick)), goal("oshkosh", 5_000, tick)])
+            .unwrap();
+        assert!(rolled.is_empty());
+    }
+
+    let tick = at(14, 0);
+    let mut rolled = exporter.export(tick, &[goal("neenah", 200_000, tick)]).unwrap();
+    rolled.sort();
+    assert_eq!(
+        rolled,
+        vec![
+            "contest-goals/page=neenah/date=2022-10-01/contest-goals-2022-10-01T13.ndjson.gz",
+            "contest-goals/page=neenah/date=2022-10-01/contest-goals-2022-10-01T13.parquet",
+            "contest-goals/page=oshkosh/date=2022-10-01/contest-goals-2022-10-01T13.ndjson.gz",
+            "contest-goals/page=oshkosh/date=2022-10-01/contest-goals-2022-10-01T13.parquet",
+        ]
+    );
+
+    // the 14:00 tick is still waiting for its hour to be over
+    assert!(dir.join("staging/contest-goals/neenah/2022-10-01T14.ndjson").exists());
+    assert!(!dir.join("staging/contest-goals/neenah/2022-10-01T13.ndjson").exists());
+    assert!(!dir.join("staging/contest-goals/oshkosh").exists());
+
+    let ndjson = std::fs::File::open(dir.join(&rolled[0])).unwrap();
+    let lines: Vec<serde_json::Value> = BufReader::new(GzDecoder::new(ndjson))
+        .lines()
+        .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[1],
+        serde_json::json!({
+            "display_name": "neenah contest",
+            "page": "neenah",
+            "goal": 10000.0,
+            "raised": 1000.51,
+            "total_entries": 42,
+            "champ_day": 0.0,
+            "timestamp": at(13, 1),
+            "ends_at": at(20, 0),
+            "velocity_per_hour": 25.0,
+            "projected_total": 1175.51,
+            "required_per_hour": null,
+        })
+    );
+
+    let parquet = SerializedFileReader::new(std::fs::File::open(dir.join(&rolled[1])).unwrap()).unwrap();
+    let columns: Vec<String> = parquet.metadata().file_metadata().schema_descr().columns().iter().map(|column| column.name().to_string()).collect();
+    assert_eq!(
+        columns,
+        vec!["display_name", "page", "goal", "raised", "total_entries", "champ_day", "timestamp", "ends_at", "velocity_per_hour", "projected_total", "required_per_hour"]
+    );
+    assert_eq!(parquet.metadata().file_metadata().num_rows(), 3);
+    let row = parquet.get_row_iter(None).unwrap().nth(1).unwrap().unwrap();
+    assert_eq!(row.get_double(9).unwrap(), 1175.51);
+    assert_eq!(row.get_column_iter().nth(10).map(|(_, field)| field), Some(&Field::Null));
+
+    let schema: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join("contest-goals/schema.json")).unwrap()).unwrap();
+    assert_eq!(schema[2], serde_json::json!({ "name": "goal", "type": "FLOAT64", "mode": "REQUIRED" }));
+    assert_eq!(schema[10], serde_json::json!({ "name": "required_per_hour", "type": "FLOAT64", "mode": "NULLABLE" }));
+}
+
+#[test]
+fn rolled_files_get_queued_for_upload() {
+    let temp = TempDir::new().unwrap();
+    let dir = temp.path();
+    let spool = Spool::new(dir.join("spool"));
+    let exporter = Exporter::new(dir.join("export"), vec![ExportFormat::Ndjson]).with_spool(spool.clone());
+
+    exporter.export(at(13, 59), &[goal("neenah", 100, at(13, 59))]).unwrap();
+    exporter.export(at(14, 0), &[goal("neenah", 100, at(14, 0))]).unwrap();
+
+    let mut pending = spool.pending().unwrap();
+    pending.sort();
+    assert_eq!(
+        pending,
+        vec![
+            "export/contest-goals/page=neenah/date=2022-10-01/contest-goals-2022-10-01T13.ndjson.gz",
+            "export/contest-goals/schema.json",
+        ]
+    );
+
+    // the partition directories go away with the last file in them
+    for name in pending {
+        spool.remove(&name).unwrap();
+    }
+    assert_eq!(std::fs::read_dir(spool.dir()).unwrap().count(), 0);
+}
+
+#[test]
+fn the_last_hour_rolls_once_everything_is_closed() {
+    let temp = TempDir::new().unwrap();
+    let dir = temp.path();
+    let exporter = Exporter::new(dir, vec![ExportFormat::Ndjson]);
+    let contests = vec![Contest { ends_at: Some(at(13, 30)), ..contest("neenah") }];
+    let mut closing_crawls = HashSet::new();
+
+    // the closing crawl is the last thing that gets staged
+    let tick = Schedule::default().plan(&contests, &closing_crawls, at(13, 59));
+    assert_eq!(tick.closing, vec!["neenah"]);
+    assert!(exporter.export(at(13, 59), &[goal("neenah", 100, at(13, 59))]).unwrap().is_empty());
+    closing_crawls.insert("neenah".to_string());
+
+    // and the idle ticks after it roll its hour once that is over
+    let tick = Schedule::default().plan(&contests, &closing_crawls, at(14, 5));
+    assert!(tick.idle());
+    assert_eq!(
+        exporter.flush::<ContestDataCSV>(at(14, 5)).unwrap(),
+        vec!["contest-goals/page=neenah/date=2022-10-01/contest-goals-2022-10-01T13.ndjson.gz"]
+    );
+    assert!(exporter.flush::<ContestDataCSV>(at(14, 20)).unwrap().is_empty());
+}
+
+#[test]
+fn export_formats_are_a_comma_separated_list() {
+    assert_eq!(ExportFormat::parse_list("").unwrap(), vec![]);
+    assert_eq!(
+        ExportFormat::parse_list("parquet, ndjson,parquet").unwrap(),
+        vec![ExportFormat::Parquet, ExportFormat::Ndjson]
+    );
+    assert!(ExportFormat::parse_list("ndjson,avro").is_err());
+
+    // off unless a format is picked
+    let temp = TempDir::new().unwrap();
+    let exporter = Exporter::new(temp.path().join("export"), vec![]);
+    assert!(!exporter.enabled());
+    assert!(exporter.export(at(13, 0), &[goal("neenah", 100, at(13, 0))]).unwrap().is_empty());
+    assert!(!exporter.dir().root().exists());
+}
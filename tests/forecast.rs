@@ -0,0 +1,77 @@
+use oshkosh_kiwanis_web_crawler::{
+    forecast::{forecast, Forecast},
+    Contests, Money,
+};
+use tempfile::TempDir;
+
+const HOUR: i64 = 60 * 60;
+
+fn dollars(points: &[(i64, i64)]) -> Vec<(i64, Money)> {
+    points.iter().map(|(hours, raised)| (hours * HOUR, Money::from_dollars(*raised))).collect()
+}
+
+#[test]
+fn pace_projects_out_to_the_end() {
+    // $200 in the last two hours with ten hours to go
+    let points = dollars(&[(0, 1_000), (1, 1_050), (2, 1_200)]);
+
+    assert_eq!(
+        forecast(&points, Money::from_dollars(3_000), Some(12 * HOUR), 2 * HOUR),
+        Forecast {
+            velocity_per_hour: Some(Money::from_dollars(100)),
+            ends_at: Some(12 * HOUR),
+            projected_total: Some(Money::from_dollars(2_200)),
+            required_per_hour: Some(Money::from_dollars(180)),
+            on_track: Some(false),
+        }
+    );
+
+    // made it already, nothing more is needed
+    let made_it = forecast(&points, Money::from_dollars(1_000), Some(12 * HOUR), 2 * HOUR);
+    assert_eq!((made_it.required_per_hour, made_it.on_track), (Some(Money::ZERO), Some(true)));
+}
+
+#[test]
+fn not_enough_to_go_on() {
+    let goal = Money::from_dollars(3_000);
+
+    // nothing crawled, one crawl, or crawls too close together to get a pace from
+    assert_eq!(forecast(&[], goal, Some(HOUR), 0), Forecast { ends_at: Some(HOUR), ..Forecast::default() });
+    assert_eq!(forecast(&dollars(&[(0, 1_000)]), goal, None, 0).velocity_per_hour, None);
+    let close = vec![(0, Money::from_dollars(1_000)), (60, Money::from_dollars(1_100))];
+    assert_eq!(forecast(&close, goal, Some(HOUR), 60).projected_total, None);
+
+    // without an end there is only the pace
+    let no_end = forecast(&dollars(&[(0, 1_000), (2, 1_200)]), goal, None, 2 * HOUR);
+    assert_eq!(no_end, Forecast { velocity_per_hour: Some(Money::from_dollars(100)), ..Forecast::default() });
+
+    // and once it is over it ends up with what it has
+    let over = forecast(&dollars(&[(0, 1_000), (2, 1_200)]), goal, Some(HOUR), 2 * HOUR);
+    assert_eq!((over.projected_total, over.required_per_hour, over.on_track), (Some(Money::from_dollars(1_200)), None, Some(false)));
+}
+
+#[test]
+fn money_going_back_is_no_pace() {
+    let points = dollars(&[(0, 1_200), (2, 1_000)]);
+
+    assert_eq!(forecast(&points, Money::from_dollars(3_000), None, 2 * HOUR).velocity_per_hour, Some(Money::ZERO));
+}
+
+#[test]
+fn contests_end_at_a_time_or_a_timestamp() {
+    let dir = TempDir::new().unwrap();
+
+    let contest = |ends_at: &str| format!("[[contests]]\ndisplay_name = \"Neenah\"\npage = \"neenah\"\nchamp_day = 0\nnum_dogs = 15\n{}\n", ends_at);
+    let load = |name: &str, contents: String| {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        Contests::from_path(&path).map(|contests| contests.get_all()[0].ends_at)
+    };
+
+    assert_eq!(load("time.toml", contest("ends_at = \"2022-10-31T20:00:00-05:00\"")).unwrap(), Some(1667264400));
+    assert_eq!(load("timestamp.toml", contest("ends_at = 1667264400")).unwrap(), Some(1667264400));
+    assert_eq!(load("none.toml", contest("")).unwrap(), None);
+
+    let error = load("bad.toml", contest("ends_at = \"Halloween\"")).unwrap_err().to_string();
+    assert!(error.contains("\"Halloween\" is not an RFC 3339 time"), "{}", error);
+}
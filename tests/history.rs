@@ -0,0 +1,166 @@
+mod common;
+
+use common::{contest, entry, NEENAH};
+use oshkosh_kiwanis_web_crawler::{
+    events::{Event, EventKind},
+    history::{Downsample, EventFilter, History},
+    ContestData, EntryData, Money,
+};
+
+// a neenah dog as it was crawled at `timestamp`
+fn crawled(dog: &str, votes: usize, timestamp: i64) -> EntryData {
+    EntryData { timestamp, ..entry(&contest(NEENAH), dog, votes) }
+}
+
+#[test]
+fn rebuilds_past_leaderboards() {
+    let mut history = History::open_in_memory().unwrap();
+
+    history.record_entries(100, &[crawled("Rex", 10, 101), crawled("Fido", 5, 102)]).unwrap();
+    history.record_entries(200, &[crawled("Rex", 12, 201), crawled("Fido", 20, 202)]).unwrap();
+
+    let dogs = |at| -> Vec<String> {
+        history.leaderboard_at(at).unwrap().into_iter().map(|r| r.dog).collect()
+    };
+
+    assert!(dogs(99).is_empty());
+    assert_eq!(dogs(150), vec!["Rex", "Fido"]);
+    assert_eq!(dogs(1000), vec!["Fido", "Rex"]);
+}
+
+#[test]
+fn past_leaderboards_have_the_contests_missing_from_the_last_crawl() {
+    let mut history = History::open_in_memory().unwrap();
+    let oahs = |dog: &str, votes, timestamp| EntryData { timestamp, ..entry(&contest("oahs"), dog, votes) };
+
+    history.record_entries(100, &[crawled("Rex", 10, 101), oahs("Spot", 30, 102)]).unwrap();
+    // oahs failed this tick, so only neenah made it in
+    history.record_entries(200, &[crawled("Rex", 40, 201), crawled("Fido", 20, 202)]).unwrap();
+    history.record_entries(300, &[oahs("Spot", 35, 301)]).unwrap();
+
+    let dogs = |at| -> Vec<(String, usize)> {
+        history.leaderboard_at(at).unwrap().into_iter().map(|r| (r.dog, r.votes)).collect()
+    };
+
+    assert_eq!(dogs(250), vec![("Rex".to_string(), 40), ("Spot".to_string(), 30), ("Fido".to_string(), 20)]);
+    assert_eq!(dogs(1000), vec![("Rex".to_string(), 40), ("Spot".to_string(), 35), ("Fido".to_string(), 20)]);
+}
+
+#[test]
+fn keeps_every_snapshot_of_an_entry() {
+    let mut history = History::open_in_memory().unwrap();
+
+    history.record_entries(100, &[crawled("Rex", 10, 101)]).unwrap();
+    history.record_entries(200, &[crawled("Rex", 12, 201)]).unwrap();
+    history.record_entries(300, &[crawled("Rex", 15, 301)]).unwrap();
+
+    // the same entry id in the next season's contest is another dog
+    history
+        .record_entries(350, &[EntryData {
+            timestamp: 351,
+            ..entry(&contest("newtopdogneenahfall2023"), "Rex", 3)
+        }])
+        .unwrap();
+
+    let votes: Vec<usize> = history.entry_history(NEENAH, "rex", 150, 400, None).unwrap().into_iter().map(|r| r.votes).collect();
+    assert_eq!(votes, vec![12, 15]);
+    assert_eq!(history.latest_entry_contest("rex").unwrap().as_deref(), Some("newtopdogneenahfall2023"));
+    assert_eq!(history.latest_entry_contest("fido").unwrap(), None);
+}
+
+#[test]
+fn keeps_every_snapshot_of_a_contest() {
+    let mut history = History::open_in_memory().unwrap();
+
+    for (timestamp, raised) in &[(100, 5000), (200, 7550)] {
+        history.record_contests(*timestamp, &[ContestData {
+            contest: contest(NEENAH),
+            goal: Money::from_dollars(1000),
+            raised: Money::from_cents(*raised),
+            total_entries: 30,
+            champ_day: Money::ZERO,
+            timestamp: *timestamp,
+            stale: false,
+            stale_age: 0,
+            forecast: None,
+        }]).unwrap();
+    }
+
+    let raised: Vec<Money> = history.contest_history(NEENAH, 0, 1000, None).unwrap().into_iter().map(|r| r.raised).collect();
+    assert_eq!(raised, vec![Money::from_dollars(50), Money::from_cents(7550)]);
+}
+
+#[test]
+fn downsamples_to_the_last_snapshot_in_each_bucket() {
+    let mut history = History::open_in_memory().unwrap();
+
+    for (timestamp, votes) in &[(0, 1), (1800, 2), (3599, 3), (3600, 4), (7000, 5)] {
+        history.record_entries(*timestamp, &[crawled("Rex", *votes, *timestamp)]).unwrap();
+    }
+
+    let points: Vec<(i64, usize)> = history
+        .entry_history(NEENAH, "rex", 0, 10_000, Some(Downsample::Hour))
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.timestamp, r.votes))
+        .collect();
+
+    assert_eq!(points, vec![(3599, 3), (7000, 5)]);
+}
+
+#[test]
+fn keeps_events_and_filters_them() {
+    let mut history = History::open_in_memory().unwrap();
+
+    let event = |kind, contest: &str, dog: &str, timestamp| Event {
+        kind,
+        contest: contest.into(),
+        entry_id: dog.to_lowercase(),
+        dog: dog.into(),
+        previous_rank: Some(2),
+        rank: Some(1),
+        previous_votes: Some(5),
+        votes: 12,
+        other_entry_id: None,
+        other_dog: None,
+        timestamp,
+    };
+    history.record_events(&[event(EventKind::NewLeader, "neenah", "Rex", 100), event(EventKind::VoteJump, "oshkosh", "Fido", 100)]).unwrap();
+    history.record_events(&[event(EventKind::Overtake, "neenah", "Spot", 200)]).unwrap();
+
+    let filter = EventFilter { from: 0, to: 1000, contest: None, kind: None, limit: 10 };
+    let dogs = |filter: EventFilter| -> Vec<String> { history.events(&filter).unwrap().into_iter().map(|e| e.dog).collect() };
+
+    assert_eq!(dogs(filter.clone()), vec!["Spot", "Fido", "Rex"]);
+    assert_eq!(dogs(EventFilter { contest: Some("neenah".into()), ..filter.clone() }), vec!["Spot", "Rex"]);
+    assert_eq!(dogs(EventFilter { kind: Some(EventKind::VoteJump), ..filter.clone() }), vec!["Fido"]);
+    assert_eq!(dogs(EventFilter { to: 150, limit: 1, ..filter.clone() }), vec!["Fido"]);
+    assert_eq!(history.events(&filter).unwrap()[2], event(EventKind::NewLeader, "neenah", "Rex", 100));
+}
+
+#[test]
+fn remembers_closing_crawls_per_crawler() {
+    let mut history = History::open_in_memory().unwrap();
+
+    history.record_closing_crawl("dogs", "neenah", 100).unwrap();
+    history.record_closing_crawl("dogs", "neenah", 160).unwrap();
+
+    assert_eq!(history.closing_crawls("dogs").unwrap().into_iter().collect::<Vec<_>>(), vec!["neenah"]);
+    assert!(history.closing_crawls("goals").unwrap().is_empty());
+}
+
+#[test]
+fn keeps_how_each_contest_ended() {
+    let mut history = History::open_in_memory().unwrap();
+    let oahs = |dog: &str, votes, timestamp| EntryData { timestamp, ..entry(&contest("oahs"), dog, votes) };
+
+    history.record_entries(100, &[crawled("Rex", 10, 101), crawled("Fido", 5, 102), oahs("Spot", 1, 103)]).unwrap();
+    // neenah closed, only oahs is still being crawled
+    history.record_entries(200, &[oahs("Spot", 7, 201)]).unwrap();
+
+    let dogs = |page| -> Vec<(String, usize)> { history.latest_entries(page).unwrap().into_iter().map(|r| (r.dog, r.votes)).collect() };
+    assert_eq!(dogs(NEENAH), vec![("Rex".to_string(), 10), ("Fido".to_string(), 5)]);
+    assert_eq!(dogs("oahs"), vec![("Spot".to_string(), 7)]);
+    assert!(dogs("misfits").is_empty());
+    assert!(history.latest_contest("oahs").unwrap().is_none());
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::{contest, entry, NEENAH};
+use oshkosh_kiwanis_web_crawler::{
+    live::{diff, LiveState, LiveUpdate},
+    ContestData, Money,
+};
+
+fn goals(raised: i64) -> Vec<ContestData> {
+    vec![ContestData {
+        contest: contest(NEENAH),
+        goal: Money::from_dollars(1000),
+        raised: Money::from_dollars(raised),
+        total_entries: 2,
+        champ_day: Money::ZERO,
+        timestamp: 0,
+        stale: false,
+        stale_age: 0,
+        forecast: None,
+    }]
+}
+
+#[test]
+fn nothing_changed_means_no_updates() {
+    let neenah = contest(NEENAH);
+    let state = LiveState { dogs: vec![entry(&neenah, "rex", 10), entry(&neenah, "fido", 5)], goals: goals(15) };
+
+    assert!(diff(&state, &state.clone()).is_empty());
+}
+
+#[test]
+fn overtake_pushes_leaderboard_totals_and_ranks() {
+    let neenah = contest(NEENAH);
+    let previous = LiveState { dogs: vec![entry(&neenah, "rex", 10), entry(&neenah, "fido", 5)], goals: goals(15) };
+    let current = LiveState { dogs: vec![entry(&neenah, "fido", 12), entry(&neenah, "rex", 10)], goals: goals(22) };
+
+    let kinds: Vec<&str> = diff(&previous, &current).iter().map(LiveUpdate::kind).collect();
+    assert_eq!(kinds, vec!["leaderboard", "contest_total", "rank", "rank"]);
+
+    let fido = diff(&previous, &current).into_iter().find(|update| matches!(update, LiveUpdate::Rank { dog, .. } if dog == "fido"));
+    match fido {
+        Some(LiveUpdate::Rank { previous_rank, rank, .. }) => assert_eq!((previous_rank, rank), (Some(2), 1)),
+        other => panic!("expected a rank update for fido, got {:?}", other),
+    }
+}
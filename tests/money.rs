@@ -0,0 +1,47 @@
+use oshkosh_kiwanis_web_crawler::{Money, money::MoneyError};
+
+fn cents(text: &str) -> i64 {
+    Money::parse(text).unwrap_or_else(|e| panic!("{}", e)).cents()
+}
+
+#[test]
+fn parses_dollars_and_cents() {
+    assert_eq!(cents("$1,234.50"), 123450);
+    assert_eq!(cents("$3,210 raised"), 321000);
+    assert_eq!(cents("Raised: $5"), 500);
+    assert_eq!(cents("$12.5"), 1250);
+    assert_eq!(cents("USD 1,000,000"), 100000000);
+}
+
+#[test]
+fn parses_other_locales() {
+    assert_eq!(cents("1.234,50 €"), 123450);
+    assert_eq!(cents("1 234,50"), 123450);
+    assert_eq!(cents("1'234.50 CHF"), 123450);
+    assert_eq!(cents("12,5"), 1250);
+    assert_eq!(cents("1.234"), 123400);
+}
+
+#[test]
+fn errors_carry_the_offending_text() {
+    let error = |text: &str| Money::parse(text).unwrap_err();
+
+    assert_eq!(error("  no donations yet "), MoneyError { text: "no donations yet".into(), reason: "there are no digits in it" });
+    assert_eq!(error("$1,234.567").reason, "it has more than two digits of cents");
+    assert_eq!(error("$12,34,567").reason, "the thousands separators are in the wrong place");
+    assert!(error("$99999999999999999999").to_string().contains("$99999999999999999999"));
+}
+
+#[test]
+fn serializes_as_dollars() {
+    assert_eq!(serde_json::to_string(&Money::from_dollars(1250)).unwrap(), "1250");
+    assert_eq!(serde_json::to_string(&Money::from_cents(123450)).unwrap(), "1234.5");
+
+    let read = |json: &str| serde_json::from_str::<Money>(json).unwrap();
+    assert_eq!(read("1250"), Money::from_dollars(1250));
+    assert_eq!(read("1234.5"), Money::from_cents(123450));
+    assert_eq!(read("\"$1,234.50\""), Money::from_cents(123450));
+
+    assert_eq!(Money::from_cents(123450).to_string(), "$1,234.50");
+    assert_eq!(Money::from_dollars(1000000).to_string(), "$1,000,000");
+}
@@ -0,0 +1,91 @@
+use oshkosh_kiwanis_web_crawler::output::{Manifest, OutputDir};
+use serde::Serialize;
+use tempfile::TempDir;
+
+fn files(output: &OutputDir) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(output.root())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[derive(Serialize)]
+struct Row {
+    dog: &'static str,
+    votes: usize,
+}
+
+#[test]
+fn writes_replace_the_whole_file_and_leave_nothing_behind() {
+    let dir = TempDir::new().unwrap();
+    let output = OutputDir::new(dir.path());
+
+    output.write("top-dogs.json", b"[1,2,3,4,5,6]").unwrap();
+    let file = output.write_json_rows("top-dogs.json", &[7, 8]).unwrap();
+
+    assert_eq!(output.read_to_string("top-dogs.json").unwrap(), "[7,8]");
+    assert_eq!((file.bytes, file.rows), (5, Some(2)));
+    // the temp files got renamed away
+    assert_eq!(files(&output), vec!["top-dogs.json"]);
+    assert_eq!(output.read_json::<Vec<u32>>("missing.json").unwrap(), None);
+}
+
+#[test]
+fn csv_files_count_their_rows() {
+    let dir = TempDir::new().unwrap();
+    let output = OutputDir::new(dir.path());
+
+    let rows = vec![Row { dog: "Biscuit", votes: 1250 }, Row { dog: "Gravy", votes: 980 }];
+    let file = output.write_csv("top-dogs.csv", rows).unwrap();
+
+    assert_eq!(output.read_to_string("top-dogs.csv").unwrap(), "dog,votes\nBiscuit,1250\nGravy,980\n");
+    assert_eq!(file.rows, Some(2));
+}
+
+#[test]
+fn claimed_files_are_out_of_the_way_of_the_next_write() {
+    let dir = TempDir::new().unwrap();
+    let output = OutputDir::new(dir.path());
+
+    output.write("top-dogs.csv", b"tick 1").unwrap();
+    let claimed = output.claim("top-dogs.csv", ".top-dogs-1.csv.uploading").unwrap().unwrap();
+    output.write("top-dogs.csv", b"tick 2").unwrap();
+
+    assert_eq!(std::fs::read_to_string(claimed).unwrap(), "tick 1");
+    assert_eq!(output.read_to_string("top-dogs.csv").unwrap(), "tick 2");
+    assert_eq!(output.claim("contest-goals.csv", ".contest-goals-1.csv.uploading").unwrap(), None);
+}
+
+#[test]
+fn manifests_tie_the_files_of_a_tick_together() {
+    let dir = TempDir::new().unwrap();
+    let output = OutputDir::new(dir.path());
+    assert_eq!(Manifest::read(&output, "dogs").unwrap(), None);
+
+    let mut manifest = Manifest::new("dogs", 1_664_000_000);
+    manifest.add(output.write_csv("top-dogs.csv", vec![Row { dog: "Biscuit", votes: 1250 }]).unwrap());
+    manifest.add(output.write_json_rows("top-dogs.json", &[1]).unwrap());
+    let written = manifest.write(&output).unwrap();
+
+    let read = Manifest::read(&output, "dogs").unwrap().unwrap();
+    assert_eq!(read, written);
+    assert_eq!(read.tick, 1_664_000_000);
+    assert_eq!(read.get("top-dogs.csv").unwrap().rows, Some(1));
+    assert_eq!(files(&output), vec!["dogs-manifest.json", "top-dogs.csv", "top-dogs.json"]);
+}
+
+#[test]
+fn a_disabled_output_dir_writes_nothing() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("output");
+    let output = OutputDir::disabled(&root);
+
+    let file = output.write_json_rows("top-dogs.json", &[1, 2]).unwrap();
+    assert_eq!(file.rows, Some(2));
+
+    assert!(!root.exists());
+    assert_eq!(output.read_json::<Vec<u32>>("top-dogs.json").unwrap(), None);
+    assert_eq!(output.claim("top-dogs.csv", ".top-dogs-1.csv.uploading").unwrap(), None);
+}
@@ -0,0 +1,57 @@
+use nipper::Document;
+use oshkosh_kiwanis_web_crawler::{
+    postback::{form_fields, next_page, parse_do_postback, Postback},
+    selectors::SelectorProfile,
+};
+
+const SEARCH_PAGE: &str = r#"
+<form id="form1" method="post" action="./search">
+  <input type="hidden" name="__EVENTTARGET" id="__EVENTTARGET" value="" />
+  <input type="hidden" name="__EVENTARGUMENT" id="__EVENTARGUMENT" value="" />
+  <input type="hidden" name="__VIEWSTATE" id="__VIEWSTATE" value="abc123" />
+  <input type="hidden" name="__EVENTVALIDATION" id="__EVENTVALIDATION" value="xyz" />
+  <div id="ContentPlaceHolder_upPanel">
+    <div class="pager">
+      <a href="javascript:__doPostBack('ctl00$ContentPlaceHolder$rptPager$ctl01$lnkPage','')">1</a>
+      <a href="javascript:__doPostBack('ctl00$ContentPlaceHolder$rptPager$ctl02$lnkPage','')">2</a>
+      <a id="ContentPlaceHolder_lnkNext" href="javascript:__doPostBack('ctl00$ContentPlaceHolder$lnkNext','')">Next</a>
+    </div>
+  </div>
+</form>
+"#;
+
+fn postback(target: &str) -> Postback {
+    Postback { event_target: target.into(), event_argument: "".into() }
+}
+
+#[test]
+fn parses_do_postback_links() {
+    assert_eq!(
+        parse_do_postback("javascript:__doPostBack('ctl00$ContentPlaceHolder$lnkNext','Page$2')"),
+        Some(Postback { event_target: "ctl00$ContentPlaceHolder$lnkNext".into(), event_argument: "Page$2".into() }),
+    );
+    assert_eq!(parse_do_postback("/newtopdogneenahfall2022/entries/1"), None);
+    assert_eq!(parse_do_postback("javascript:__doPostBack('','')"), None);
+}
+
+#[test]
+fn prefers_the_numbered_page_then_the_next_link() {
+    let doc = Document::from(SEARCH_PAGE);
+
+    let selectors = SelectorProfile::default();
+
+    assert_eq!(next_page(&doc, &selectors, 2), Some(postback("ctl00$ContentPlaceHolder$rptPager$ctl02$lnkPage")));
+    assert_eq!(next_page(&doc, &selectors, 3), Some(postback("ctl00$ContentPlaceHolder$lnkNext")));
+}
+
+#[test]
+fn posts_back_the_view_state_with_the_event() {
+    let doc = Document::from(SEARCH_PAGE);
+    let fields = form_fields(&doc, &SelectorProfile::default(), &postback("ctl00$ContentPlaceHolder$lnkNext"));
+
+    let field = |name: &str| fields.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+    assert_eq!(field("__VIEWSTATE"), Some("abc123"));
+    assert_eq!(field("__EVENTVALIDATION"), Some("xyz"));
+    assert_eq!(field("__EVENTTARGET"), Some("ctl00$ContentPlaceHolder$lnkNext"));
+    assert_eq!(fields.iter().filter(|(n, _)| n == "__EVENTTARGET").count(), 1);
+}
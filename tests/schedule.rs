@@ -0,0 +1,97 @@
+mod common;
+
+use std::{collections::HashSet, time::Duration};
+
+use common::contest;
+use oshkosh_kiwanis_web_crawler::{
+    schedule::{Schedule, CRAWL_INTERVAL},
+    Contest, ContestStatus, Contests,
+};
+use tempfile::TempDir;
+
+const HOUR: i64 = 60 * 60;
+
+fn dated(page: &str, starts_at: Option<i64>, ends_at: Option<i64>) -> Contest {
+    Contest { starts_at, ends_at, ..contest(page) }
+}
+
+fn pages(contests: &[Contest]) -> Vec<&str> {
+    contests.iter().map(|contest| contest.page.as_str()).collect()
+}
+
+#[test]
+fn contests_are_live_between_their_dates() {
+    let neenah = dated("neenah", Some(10 * HOUR), Some(20 * HOUR));
+
+    assert_eq!(neenah.status(9 * HOUR), ContestStatus::Upcoming);
+    assert_eq!(neenah.status(10 * HOUR), ContestStatus::Live);
+    assert_eq!(neenah.status(20 * HOUR), ContestStatus::Closed);
+
+    // the voting can stop before the contest is over
+    let early = Contest { voting_closes_at: Some(18 * HOUR), ..neenah };
+    assert_eq!(early.status(18 * HOUR), ContestStatus::Closed);
+
+    // and without dates there is no telling, so it is always live
+    assert_eq!(dated("oshkosh", None, None).status(0), ContestStatus::Live);
+}
+
+#[test]
+fn closed_contests_get_one_last_crawl() {
+    let contests = vec![
+        dated("live", None, None),
+        dated("closing", Some(0), Some(10 * HOUR)),
+        dated("closed", Some(0), Some(5 * HOUR)),
+        dated("upcoming", Some(30 * HOUR), None),
+    ];
+    let closing_crawls: HashSet<String> = vec!["closed".to_string()].into_iter().collect();
+
+    let tick = Schedule::default().plan(&contests, &closing_crawls, 11 * HOUR);
+    assert_eq!(pages(&tick.crawl), vec!["live", "closing"]);
+    assert_eq!(tick.closing, vec!["closing"]);
+    assert_eq!(tick.closed, vec!["closed"]);
+    assert_eq!(tick.wait, CRAWL_INTERVAL);
+
+    // a closing crawl that keeps failing is given up on after a day
+    let tick = Schedule::default().plan(&contests, &closing_crawls, 35 * HOUR);
+    assert_eq!(tick.closed, vec!["closing", "closed"]);
+}
+
+#[test]
+fn nothing_open_is_idle_until_the_next_start() {
+    let schedule = Schedule { idle_interval: Duration::from_secs(15 * 60) };
+    let contests = vec![dated("closed", Some(0), Some(HOUR)), dated("upcoming", Some(10 * HOUR), None)];
+    let closing_crawls: HashSet<String> = vec!["closed".to_string()].into_iter().collect();
+
+    let tick = schedule.plan(&contests, &closing_crawls, 2 * HOUR);
+    assert!(tick.idle());
+    assert_eq!(tick.wait, Duration::from_secs(15 * 60));
+
+    // but wakes up right when the next one opens
+    let tick = schedule.plan(&contests, &closing_crawls, 10 * HOUR - 60);
+    assert_eq!(tick.wait, Duration::from_secs(60));
+    assert_eq!(pages(&schedule.plan(&contests, &closing_crawls, 10 * HOUR).crawl), vec!["upcoming"]);
+}
+
+#[test]
+fn contests_have_to_start_before_they_close() {
+    let dir = TempDir::new().unwrap();
+
+    let load = |name: &str, dates: &str| {
+        let path = dir.path().join(name);
+        let contents = format!("[[contests]]\ndisplay_name = \"Neenah\"\npage = \"neenah\"\nchamp_day = 0\nnum_dogs = 15\n{}\n", dates);
+        std::fs::write(&path, contents).unwrap();
+        Contests::from_path(&path).map(|contests| contests.get_all()[0].clone())
+    };
+
+    let neenah = load(
+        "ok.toml",
+        "starts_at = \"2022-09-01T00:00:00-05:00\"\nvoting_closes_at = \"2022-10-30T20:00:00-05:00\"\nends_at = \"2022-10-31T20:00:00-05:00\"",
+    )
+    .unwrap();
+    assert_eq!((neenah.starts_at, neenah.closes_at()), (Some(1662008400), Some(1667178000)));
+
+    let backwards = load("backwards.toml", "starts_at = 1667264400\nends_at = 1662008400").unwrap_err().to_string();
+    assert!(backwards.contains("the contest has to start before it closes"), "{}", backwards);
+    let late_vote = load("late.toml", "voting_closes_at = 1667264401\nends_at = 1667264400").unwrap_err().to_string();
+    assert!(late_vote.contains("voting_closes_at must not be after ends_at"), "{}", late_vote);
+}
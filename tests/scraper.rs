@@ -0,0 +1,155 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{contest, fixture, MockSite, NEENAH};
+use oshkosh_kiwanis_web_crawler::{
+    fetch::{CrawlLimits, Fetcher},
+    scraper::{ContestSummary, CrawlDepth, GogoPhotoClient, ScrapeError},
+    selectors::SelectorProfile,
+    tasks::{dogs, goals},
+    Contest, Money,
+};
+
+fn client(site: &MockSite) -> GogoPhotoClient {
+    let limits = CrawlLimits { requests_per_second: 0.0, retries: 0, ..CrawlLimits::default() };
+    GogoPhotoClient::new(Fetcher::new(limits, Duration::from_secs(5)).unwrap(), &site.domain)
+}
+
+fn neenah(num_dogs: usize) -> Contest {
+    Contest {
+        display_name: "Neenah's NEW Top Dog Fall 2022".into(),
+        champ_day: Money::from_dollars(100),
+        num_dogs,
+        aliases: vec!["Neenah".into()],
+        ..contest(NEENAH)
+    }
+}
+
+#[tokio::test]
+async fn reads_every_field_of_an_entry_page() {
+    let site = MockSite::neenah().await;
+    let url = format!("{}/newtopdogneenahfall2022/entries/101", site.domain);
+
+    let dog = client(&site).entry_detail(&neenah(15), &url).await.unwrap();
+
+    assert_eq!(dog.dog, "Biscuit");
+    assert_eq!(dog.votes, 1250);
+    assert_eq!(dog.raised, Money::from_dollars(1250));
+    assert_eq!(dog.category, "Neenah");
+    assert_eq!(dog.picture, format!("{}/photos/entries/101.jpg", site.domain));
+    assert_eq!(dog.entry_id(), "101");
+    assert!(!dog.stale);
+}
+
+#[tokio::test]
+async fn missing_entry_pages_are_request_errors() {
+    let site = MockSite::neenah().await;
+    let url = format!("{}/newtopdogneenahfall2022/entries/999", site.domain);
+
+    match client(&site).entry_detail(&neenah(15), &url).await {
+        Err(ScrapeError::Request { url: failed, .. }) => assert_eq!(failed, url),
+        other => panic!("expected a request error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn changed_pages_are_errors_instead_of_zero_votes() {
+    let changed = fixture("entry-101.html").replace("viewEntryVotes", "entryVoteCount");
+    let garbled = fixture("entry-102.html").replace("980 Votes", "lots of Votes");
+    let site = MockSite::start(vec![("GET /changed", changed), ("GET /garbled", garbled)]).await;
+
+    let client = client(&site);
+
+    match client.entry_detail(&neenah(15), &format!("{}/changed", site.domain)).await {
+        Err(ScrapeError::Missing { field, .. }) => assert_eq!(field, "entry_votes"),
+        other => panic!("expected a missing votes error, got {:?}", other),
+    }
+
+    match client.entry_detail(&neenah(15), &format!("{}/garbled", site.domain)).await {
+        Err(ScrapeError::Parse { field, text, .. }) => assert_eq!((field, text.as_str()), ("entry_votes", "lots of Votes")),
+        other => panic!("expected a votes parse error, got {:?}", other),
+    }
+
+    // only the selector that matched nothing makes the scrape unhealthy
+    let health = client.take_health();
+    assert!(!health.healthy);
+    assert_eq!(health.missing.into_iter().collect::<Vec<_>>(), vec![("entry_votes".to_string(), 1)]);
+    assert!(client.take_health().healthy);
+}
+
+#[tokio::test]
+async fn selectors_can_be_overridden() {
+    let changed = fixture("entry-101.html").replace("viewEntryVotes", "entryVoteCount");
+    let site = MockSite::start(vec![("GET /changed", changed)]).await;
+
+    let selectors = SelectorProfile { entry_votes: "h3.entryVoteCount".into(), ..SelectorProfile::default() };
+    let dog = client(&site).with_selectors(selectors).entry_detail(&neenah(15), &format!("{}/changed", site.domain)).await.unwrap();
+
+    assert_eq!(dog.votes, 1250);
+}
+
+#[tokio::test]
+async fn searches_the_top_dogs_in_leaderboard_order() {
+    let site = MockSite::neenah().await;
+
+    let results = client(&site).search_entries(&neenah(2), CrawlDepth::Top).await.unwrap();
+
+    let ids: Vec<&str> = results.entry_urls.iter().map(|url| url.rsplit('/').next().unwrap()).collect();
+    assert_eq!(ids, vec!["101", "102"]);
+    assert_eq!(results.coverage.pages, 1);
+    assert_eq!(results.coverage.total_entries, Some(4));
+    assert!(site.requests().iter().all(|request| request.method == "GET"));
+}
+
+#[tokio::test]
+async fn only_full_crawls_need_the_total_entries() {
+    let site = MockSite::neenah().await;
+    let client = client(&site).with_selectors(SelectorProfile { search_total: "span.noSuchTotal".into(), ..SelectorProfile::default() });
+
+    let results = client.search_entries(&neenah(2), CrawlDepth::Top).await.unwrap();
+    assert_eq!((results.entry_urls.len(), results.coverage.total_entries), (2, None));
+
+    match client.search_entries(&neenah(2), CrawlDepth::Full { max_pages: 10 }).await {
+        Err(ScrapeError::Missing { field, .. }) => assert_eq!(field, "search_total"),
+        other => panic!("expected the total entries to be missing, got {:?}", other.map(|results| results.coverage)),
+    }
+}
+
+#[tokio::test]
+async fn full_crawl_follows_the_pagination() {
+    let site = MockSite::neenah().await;
+
+    let (dogs, coverage) = dogs::crawl_site(&client(&site), neenah(2), CrawlDepth::Full { max_pages: 10 }).await.unwrap();
+
+    let names: Vec<&str> = dogs.iter().map(|dog| dog.dog.as_str()).collect();
+    assert_eq!(names, vec!["Biscuit", "Pepper", "Moose", "Waffles"]);
+    assert_eq!((coverage.found, coverage.total_entries, coverage.pages, coverage.capped), (4, Some(4), 2, false));
+
+    // the postback has to carry the view state of the first page
+    let postback = site.requests().into_iter().find(|request| request.method == "POST").expect("a postback for page 2");
+    assert!(postback.body.contains("__VIEWSTATE="));
+    assert!(postback.body.contains("__EVENTTARGET=ctl00%24ContentPlaceHolder%24rptPager%24ctl01%24lnkPage"));
+}
+
+#[tokio::test]
+async fn full_crawl_stops_at_the_page_cap() {
+    let site = MockSite::neenah().await;
+
+    let results = client(&site).search_entries(&neenah(2), CrawlDepth::Full { max_pages: 1 }).await.unwrap();
+
+    assert_eq!(results.entry_urls.len(), 3);
+    assert!(results.coverage.capped);
+}
+
+#[tokio::test]
+async fn reads_the_contest_summary() {
+    let site = MockSite::neenah().await;
+
+    let summary = client(&site).contest_summary(&neenah(15)).await.unwrap();
+    assert_eq!(summary, ContestSummary { raised: Money::from_cents(321050), goal: Money::from_dollars(5000), total_entries: 4 });
+
+    let goal = goals::crawl_site(&client(&site), neenah(15)).await.unwrap();
+    assert_eq!((goal.raised, goal.goal, goal.total_entries), (Money::from_cents(321050), Money::from_dollars(5000), 4));
+    assert_eq!(goal.champ_day, Money::from_dollars(100));
+}
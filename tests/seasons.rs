@@ -0,0 +1,148 @@
+use oshkosh_kiwanis_web_crawler::{
+    history::History,
+    seasons::{compare, shelter_seasons, Comparison},
+    Contest, ContestData, Contests, Money,
+};
+use tempfile::TempDir;
+
+const ROSTER: &str = r#"
+seasons = ["Fall 2021", "Fall 2022"]
+
+[[contests]]
+display_name = "Neenah Fall 2021"
+page = "newtopdogneenahfall2021"
+season = "Fall 2021"
+shelter = "neenah"
+champ_day = 0
+num_dogs = 15
+
+[[contests]]
+display_name = "Oshkosh Fall 2021"
+page = "newtopdogoahsfall2021"
+season = "Fall 2021"
+shelter = "oahs"
+champ_day = 0
+num_dogs = 15
+
+[[contests]]
+display_name = "Neenah Fall 2022"
+page = "newtopdogneenahfall2022"
+season = "Fall 2022"
+shelter = "neenah"
+champ_day = 0
+num_dogs = 15
+
+[[contests]]
+display_name = "Lakeshore Fall 2022"
+page = "newtopdoglakeshorefall2022"
+season = "Fall 2022"
+champ_day = 0
+num_dogs = 15
+"#;
+
+fn roster(contents: &str) -> Result<Contests, String> {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("contests.toml");
+    std::fs::write(&path, contents).unwrap();
+
+    Contests::from_path(&path).map_err(|e| e.to_string())
+}
+
+fn pages(contests: &Contests) -> Vec<String> {
+    contests.get_all().into_iter().map(|contest| contest.page).collect()
+}
+
+// the contest as it ended up, with some champ day money on top
+fn crawl(history: &mut History, contest: &Contest, raised: i64, entries: usize) {
+    history
+        .record_contests(100, &[ContestData {
+            contest: contest.clone(),
+            goal: Money::from_dollars(5_000),
+            raised: Money::from_dollars(raised - 100),
+            total_entries: entries,
+            champ_day: Money::from_dollars(100),
+            timestamp: 100,
+            stale: false,
+            stale_age: 0,
+            forecast: None,
+        }])
+        .unwrap();
+}
+
+#[test]
+fn only_the_newest_season_is_current() {
+    let contests = roster(ROSTER).unwrap();
+
+    assert_eq!(contests.seasons(), ["Fall 2021", "Fall 2022"]);
+    assert_eq!(contests.current_season(), "Fall 2022");
+    assert_eq!(contests.previous_season("Fall 2022"), Some("Fall 2021"));
+    assert_eq!(contests.previous_season("Fall 2021"), None);
+
+    assert_eq!(pages(&contests.current()), vec!["newtopdogneenahfall2022", "newtopdoglakeshorefall2022"]);
+    assert_eq!(pages(&contests.season("Fall 2021").unwrap()), vec!["newtopdogneenahfall2021", "newtopdogoahsfall2021"]);
+    assert!(contests.season("Spring 2022").is_none());
+
+    // without a list the seasons go in the order they show up in
+    let unlisted = roster(&ROSTER.replace(r#"seasons = ["Fall 2021", "Fall 2022"]"#, "")).unwrap();
+    assert_eq!(unlisted.seasons(), ["Fall 2021", "Fall 2022"]);
+}
+
+#[test]
+fn contests_have_to_be_in_a_listed_season() {
+    let error = roster(&ROSTER.replace(r#"season = "Fall 2021""#, r#"season = "Fall 2020""#)).unwrap_err();
+    assert!(error.contains("contest #1") && error.contains("season must be one of the seasons"), "{}", error);
+
+    let error = roster(&ROSTER.replace(r#""Fall 2021", "Fall 2022""#, r#""Fall 2021", "Fall 2021""#)).unwrap_err();
+    assert!(error.contains("season \"Fall 2021\" is listed more than once"), "{}", error);
+}
+
+#[test]
+fn shelters_are_compared_with_the_season_before() {
+    let contests = roster(ROSTER).unwrap();
+    let all = contests.get_all();
+    let mut history = History::open_in_memory().unwrap();
+    crawl(&mut history, &all[0], 4_000, 40);
+    crawl(&mut history, &all[1], 3_000, 30);
+    crawl(&mut history, &all[2], 5_000, 45);
+    crawl(&mut history, &all[3], 1_000, 10);
+
+    let shelters = compare(&contests, &history, "Fall 2022", "Fall 2021").unwrap();
+    let summary: Vec<(&str, bool, bool)> = shelters.iter().map(|c| (c.shelter.as_str(), c.current.is_some(), c.previous.is_some())).collect();
+    assert_eq!(
+        summary,
+        vec![("neenah", true, true), ("oahs", false, true), ("newtopdoglakeshorefall2022", true, false)]
+    );
+
+    let neenah = &shelters[0];
+    assert_eq!(neenah.current.as_ref().unwrap().raised, Money::from_dollars(5_000));
+    assert_eq!((neenah.raised_change, neenah.raised_change_percent, neenah.entries_change), (Some(Money::from_dollars(1_000)), Some(25.0), Some(5)));
+    assert_eq!(shelters[1].raised_change, None);
+
+    let seasons = shelter_seasons(&contests, &history, "neenah").unwrap();
+    let seasons: Vec<(String, Option<Money>)> = seasons.iter().map(|c: &Comparison| (c.current.as_ref().unwrap().season.clone(), c.raised_change)).collect();
+    assert_eq!(seasons, vec![("Fall 2021".to_string(), None), ("Fall 2022".to_string(), Some(Money::from_dollars(1_000)))]);
+}
+
+#[test]
+fn shelters_that_sit_a_season_out_have_nothing_to_compare_with() {
+    // oahs had no contest in fall 2022
+    let roster_2023 = ROSTER.replace(r#""Fall 2021", "Fall 2022""#, r#""Fall 2021", "Fall 2022", "Fall 2023""#)
+        + r#"
+[[contests]]
+display_name = "Oshkosh Fall 2023"
+page = "newtopdogoahsfall2023"
+season = "Fall 2023"
+shelter = "oahs"
+champ_day = 0
+num_dogs = 15
+"#;
+    let contests = roster(&roster_2023).unwrap();
+    let all = contests.get_all();
+    let mut history = History::open_in_memory().unwrap();
+    crawl(&mut history, &all[1], 3_000, 30);
+    crawl(&mut history, &all[4], 3_500, 35);
+
+    let seasons = shelter_seasons(&contests, &history, "oahs").unwrap();
+    let seasons: Vec<(String, bool)> = seasons.iter().map(|c| (c.current.as_ref().unwrap().season.clone(), c.previous.is_some())).collect();
+    assert_eq!(seasons, vec![("Fall 2021".to_string(), false), ("Fall 2023".to_string(), false)]);
+}
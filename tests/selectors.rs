@@ -0,0 +1,62 @@
+use std::io::Write;
+
+use oshkosh_kiwanis_web_crawler::selectors::{PageKind, SelectorProfile};
+use tempfile::NamedTempFile;
+
+// gone again once the test is done with it
+fn profile_file(extension: &str, content: &str) -> NamedTempFile {
+    let mut file = tempfile::Builder::new().prefix("selectors-").suffix(&format!(".{}", extension)).tempfile().unwrap();
+    file.write_all(content.as_bytes()).unwrap();
+    file
+}
+
+#[test]
+fn profile_files_only_need_the_selectors_they_change() {
+    let file = profile_file("toml", "entry_votes = \"span.voteCount\"\n");
+    let profile = SelectorProfile::from_path(file.path()).unwrap();
+
+    assert_eq!(profile.entry_votes, "span.voteCount");
+    assert_eq!(profile.entry_raised, SelectorProfile::default().entry_raised);
+}
+
+#[test]
+fn invalid_selectors_are_rejected_when_loading() {
+    let file = profile_file("json", r#"{"contest_goal": "div.goal >> span["}"#);
+    let error = SelectorProfile::from_path(file.path()).unwrap_err().to_string();
+
+    assert!(error.contains("contest_goal"), "{}", error);
+}
+
+#[test]
+fn checks_report_which_fields_resolved() {
+    let html = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/gogophoto/contest.html")).unwrap();
+    let checks = SelectorProfile::default().check(PageKind::Contest, &html);
+
+    let resolved: Vec<(&str, Option<&str>)> = checks.iter().map(|check| (check.field, check.value.as_deref())).collect();
+    assert_eq!(resolved, vec![("contest_raised", Some("$3,210.50")), ("contest_goal", Some("$5,000"))]);
+
+    let entry_checks = SelectorProfile::default().check(PageKind::Entry, &html);
+    let votes = entry_checks.iter().find(|check| check.field == "entry_votes").unwrap();
+    assert_eq!((votes.matches, votes.resolved()), (0, false));
+}
+
+#[test]
+fn checks_report_the_pager_and_the_view_state() {
+    let html = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/gogophoto/search.html")).unwrap();
+    let checks = SelectorProfile::default().check(PageKind::Search, &html);
+
+    let check = |field: &str| checks.iter().find(|check| check.field == field).unwrap();
+    assert_eq!((check("search_pager").matches, check("search_pager").value.as_deref()), (2, Some("2 Next ›")));
+    assert_eq!(
+        check("search_state").value.as_deref(),
+        Some("__EVENTTARGET __EVENTARGUMENT __VIEWSTATE __VIEWSTATEGENERATOR __EVENTVALIDATION")
+    );
+}
+
+#[test]
+fn guesses_the_kind_of_page() {
+    assert_eq!(PageKind::guess("https://www.gogophotocontest.com/newtopdogneenahfall2022/entries/101"), PageKind::Entry);
+    assert_eq!(PageKind::guess("https://www.gogophotocontest.com/newtopdogneenahfall2022/search"), PageKind::Search);
+    assert_eq!(PageKind::guess("https://www.gogophotocontest.com/newtopdogneenahfall2022"), PageKind::Contest);
+    assert_eq!(PageKind::guess("tests/fixtures/gogophoto/entry-101.html"), PageKind::Entry);
+}
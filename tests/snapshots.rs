@@ -0,0 +1,53 @@
+mod common;
+
+use common::{contest, entry, NEENAH};
+use oshkosh_kiwanis_web_crawler::{snapshots::SnapshotStore, ContestData, Money};
+
+fn goal(raised: i64) -> ContestData {
+    ContestData {
+        contest: contest(NEENAH),
+        goal: Money::from_dollars(5000),
+        raised: Money::from_dollars(raised),
+        total_entries: 4,
+        champ_day: Money::ZERO,
+        timestamp: 100,
+        stale: false,
+        stale_age: 0,
+        forecast: None,
+    }
+}
+
+#[test]
+fn nothing_is_there_until_it_is_published() {
+    let store = SnapshotStore::default();
+
+    assert_eq!(store.version(), 0);
+    assert!(store.dogs().is_none());
+    assert!(store.goals().is_none());
+    assert!(store.live_state().is_none());
+}
+
+#[test]
+fn every_publish_gets_a_newer_version_every_clone_can_see() {
+    let store = SnapshotStore::default();
+    let api = store.clone();
+    let neenah = contest(NEENAH);
+
+    let first = store.publish_dogs(100, vec![entry(&neenah, "Biscuit", 1250), entry(&neenah, "Gravy", 980)], vec![entry(&neenah, "Biscuit", 1250)]);
+    let second = store.publish_goals(100, vec![goal(3210)]);
+    assert!(second > first);
+    assert_eq!(api.version(), second);
+
+    let dogs = api.dogs().unwrap();
+    assert_eq!((dogs.version, dogs.tick, dogs.data.len()), (first, 100, 2));
+    assert_eq!(api.leaderboard().unwrap().to_vec()[0].dog, "Biscuit");
+    assert_eq!(api.goals().unwrap().data[0].raised, Money::from_dollars(3210));
+
+    let state = api.live_state().unwrap();
+    assert_eq!((state.dogs.len(), state.goals.len()), (2, 1));
+
+    // a snapshot that was handed out doesn't change under whoever has it
+    store.publish_dogs(160, vec![entry(&neenah, "Gravy", 1300)], vec![entry(&neenah, "Gravy", 1300)]);
+    assert_eq!(dogs.data[0].dog, "Biscuit");
+    assert_eq!(api.dogs().unwrap().tick, 160);
+}
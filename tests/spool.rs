@@ -0,0 +1,121 @@
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+};
+
+use futures::{executor::block_on, future::LocalBoxFuture};
+use oshkosh_kiwanis_web_crawler::{
+    sinks::Sink,
+    spool::{content_hash, mime_type, Dedupe, Spool, Upload},
+    tasks::upload::drain,
+};
+use tempfile::TempDir;
+
+// takes uploads until it is told to go down
+#[derive(Default)]
+struct FlakySink {
+    down: Cell<bool>,
+    uploaded: RefCell<Vec<(String, String)>>,
+}
+
+impl Sink for FlakySink {
+    fn describe(&self) -> String {
+        "flaky".into()
+    }
+
+    fn put<'a>(&'a self, name: &'a str, body: Vec<u8>, mime_type: &'a str) -> LocalBoxFuture<'a, Result<(), Box<dyn Error>>> {
+        Box::pin(async move {
+            if self.down.get() {
+                return Err("503 Service Unavailable".into());
+            }
+
+            assert_eq!(mime_type, "text/csv");
+            self.uploaded.borrow_mut().push((name.to_string(), String::from_utf8(body).unwrap()));
+            Ok(())
+        })
+    }
+}
+
+#[test]
+fn pending_files_come_out_oldest_first() {
+    let dir = TempDir::new().unwrap();
+    let spool = Spool::new(dir.path());
+    assert!(spool.pending().unwrap().is_empty());
+
+    spool.enqueue("top-dogs-1664000120.csv", b"3").unwrap();
+    spool.enqueue("top-dogs-1664000060.csv", b"1").unwrap();
+    spool.enqueue("contest-goals-1664000060.csv", b"2").unwrap();
+    // a write that hasn't finished yet
+    std::fs::write(spool.dir().join(".top-dogs-1664000180.csv.1-0.tmp"), "4").unwrap();
+
+    assert_eq!(
+        spool.pending().unwrap(),
+        vec!["contest-goals-1664000060.csv", "top-dogs-1664000060.csv", "top-dogs-1664000120.csv"]
+    );
+    assert_eq!(mime_type("top-dogs-1664000060.csv"), "text/csv");
+    assert_eq!(mime_type("dogs-manifest.json"), "application/json");
+}
+
+#[test]
+fn files_only_leave_the_spool_once_they_are_uploaded() {
+    let dir = TempDir::new().unwrap();
+    let spool = Spool::new(dir.path());
+    let sink = FlakySink::default();
+
+    spool.enqueue("top-dogs-1664000060.csv", b"tick 1").unwrap();
+    spool.enqueue("top-dogs-1664000120.csv", b"tick 2").unwrap();
+
+    sink.down.set(true);
+    let drained = block_on(drain(&sink, &spool));
+    assert_eq!((drained.uploaded, drained.pending), (0, 2));
+    assert_eq!(drained.failed.unwrap().0, "top-dogs-1664000060.csv");
+    assert_eq!(spool.pending().unwrap().len(), 2);
+
+    // the outage is over and another tick got queued in the meantime
+    spool.enqueue("top-dogs-1664000180.csv", b"tick 3").unwrap();
+    sink.down.set(false);
+    let drained = block_on(drain(&sink, &spool));
+    assert_eq!((drained.uploaded, drained.pending), (3, 0));
+    assert!(drained.failed.is_none());
+    assert!(spool.pending().unwrap().is_empty());
+
+    let uploaded: Vec<String> = sink.uploaded.borrow().iter().map(|(_, body)| body.clone()).collect();
+    assert_eq!(uploaded, vec!["tick 1", "tick 2", "tick 3"]);
+}
+
+#[test]
+fn hashes_leave_out_the_timestamps() {
+    let tick_1 = b"dog,votes,timestamp\nBiscuit,1250,1664000060\n";
+    let tick_2 = b"dog,votes,timestamp\nBiscuit,1250,1664000120\n";
+    let tick_3 = b"dog,votes,timestamp\nBiscuit,1251,1664000180\n";
+
+    assert_eq!(content_hash("top-dogs-1.csv", tick_1), content_hash("top-dogs-2.csv", tick_2));
+    assert_ne!(content_hash("top-dogs-2.csv", tick_2), content_hash("top-dogs-3.csv", tick_3));
+    // the forecasts move with the clock too
+    let goals_1 = b"page,raised,timestamp,velocity_per_hour,projected_total\nneenah,500,1664000060,25,900\n";
+    let goals_2 = b"page,raised,timestamp,velocity_per_hour,projected_total\nneenah,500,1664000120,24.5,890\n";
+    assert_eq!(content_hash("contest-goals-1.csv", goals_1), content_hash("contest-goals-2.csv", goals_2));
+    // anything that isn't csv is hashed as it is
+    assert_ne!(content_hash("top-dogs-1.json", tick_1), content_hash("top-dogs-2.json", tick_2));
+}
+
+#[test]
+fn unchanged_snapshots_only_go_up_for_the_heartbeat() {
+    let mut dedupe = Dedupe::new(6);
+    let hour = 60 * 60;
+    let dogs = |timestamp: i64, votes: usize| format!("dog,votes,timestamp\nBiscuit,{},{}\n", votes, timestamp).into_bytes();
+
+    assert_eq!(dedupe.check("top-dogs-0.csv", &dogs(0, 1250), 0), Upload::Changed);
+    assert_eq!(dedupe.check("top-dogs-60.csv", &dogs(60, 1250), 60), Upload::Unchanged);
+    // the other kinds of snapshot are kept track of on their own
+    assert_eq!(dedupe.check("contest-goals-60.csv", &dogs(60, 1250), 60), Upload::Changed);
+    assert_eq!(dedupe.check("top-dogs-120.csv", &dogs(120, 1251), 120), Upload::Changed);
+
+    assert_eq!(dedupe.check("top-dogs-1.csv", &dogs(6 * hour, 1251), 6 * hour), Upload::Unchanged);
+    assert_eq!(dedupe.check("top-dogs-2.csv", &dogs(6 * hour + 120, 1251), 6 * hour + 120), Upload::Heartbeat);
+    assert_eq!(dedupe.check("top-dogs-3.csv", &dogs(6 * hour + 180, 1251), 6 * hour + 180), Upload::Unchanged);
+
+    // a snapshot that never made it in to the spool doesn't count
+    dedupe.forget("top-dogs-3.csv");
+    assert_eq!(dedupe.check("top-dogs-4.csv", &dogs(6 * hour + 240, 1251), 6 * hour + 240), Upload::Changed);
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use std::error::Error;
+
+use common::{contest, entry};
+use oshkosh_kiwanis_web_crawler::{
+    fetch::ContestTiming,
+    stale::{ContestCrawl, LastGood},
+    EntryData,
+};
+
+// a dog as it was crawled at `timestamp`
+fn crawled(page: &str, dog: &str, timestamp: i64) -> EntryData {
+    EntryData { timestamp, ..entry(&contest(page), dog, 10) }
+}
+
+fn crawl(page: &str, result: Result<Vec<EntryData>, Box<dyn Error>>) -> ContestCrawl<EntryData> {
+    let timing = ContestTiming {
+        page: page.into(),
+        duration_ms: 0,
+        results: result.as_ref().map_or(0, Vec::len),
+        attempts: 1,
+        ok: result.is_ok(),
+    };
+
+    (timing, result)
+}
+
+#[test]
+fn failed_contests_carry_forward_their_last_good_data() {
+    let mut last_good = LastGood::default();
+    last_good.merge(100, vec![crawl("neenah", Ok(vec![crawled("neenah", "rex", 100)])), crawl("oahs", Ok(vec![crawled("oahs", "fido", 100)]))]);
+
+    let (results, errors) = last_good.merge(160, vec![crawl("neenah", Ok(vec![crawled("neenah", "rex", 160)])), crawl("oahs", Err("timed out".into()))]);
+
+    let fido = results.iter().find(|dog| dog.dog == "fido").expect("fido should be carried forward");
+    assert!(fido.stale);
+    assert_eq!(fido.stale_age, 60);
+    assert!(results.iter().filter(|dog| dog.dog == "rex").all(|dog| !dog.stale));
+
+    assert_eq!(errors.crawled, vec!["neenah"]);
+    assert_eq!(errors.failed.len(), 1);
+    assert_eq!(errors.failed[0].page, "oahs");
+    assert_eq!(errors.failed[0].last_good, Some(100));
+}
+
+#[test]
+fn contests_without_good_data_are_left_out() {
+    let mut last_good = LastGood::seed(vec![crawled("neenah", "rex", 100)]);
+
+    let (results, errors) = last_good.merge(160, vec![crawl("neenah", Err("boom".into())), crawl("oahs", Err("boom".into()))]);
+
+    assert_eq!(results.iter().map(|dog| dog.dog.as_str()).collect::<Vec<_>>(), vec!["rex"]);
+    assert_eq!(errors.failed[1].last_good, None);
+    assert!(errors.nothing_crawled());
+}
+
+#[test]
+fn closed_contests_carry_forward_as_they_ended() {
+    let last_good = LastGood::seed(vec![crawled("neenah", "rex", 100), crawled("oahs", "fido", 100)]);
+
+    let closed = last_good.closed(&["oahs".to_string(), "misfits".to_string()]);
+    assert_eq!(closed.iter().map(|dog| (dog.dog.as_str(), dog.stale)).collect::<Vec<_>>(), vec![("fido", false)]);
+}